@@ -0,0 +1,190 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use anyhow::Context;
+use crate::models::{ProblemSpec, Solution};
+use crate::optimizer::annealing::{AnnealOptions, Schedule};
+use crate::optimizer::grid::Packing;
+use crate::optimizer::z3::Z3Options;
+use crate::store::{problem_id, Record, SolutionStore};
+use crate::export::{self, ImageFormat};
+use crate::{optimizer, progress, scoring, validation};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Optimizer {
+  /// Sequential particle swarm
+  Swarm,
+  /// Hungarian assignment onto grid slots
+  Assign,
+  /// Hungarian assignment onto hex grid slots
+  Grid,
+  /// Stage edges facing the most audience first
+  Perimeter,
+  /// Grid-slot assignment refined by simulated annealing
+  Anneal,
+  /// z3 with a binned objective and a one minute timeout
+  Z3,
+}
+
+pub struct BatchResult {
+  pub problem_id: u32,
+  pub musicians: usize,
+  pub attendees: usize,
+  pub score: Result<i64, anyhow::Error>,
+  /// Whether the solution beat the stored best
+  pub promoted: bool,
+  pub runtime: Duration,
+}
+
+/// Runs an optimizer on a problem and tunes volumes on the result
+pub fn solve(problem: &ProblemSpec, optimizer: Optimizer) -> Result<Solution, anyhow::Error> {
+  let placements = match optimizer {
+    Optimizer::Swarm => optimizer::particle_swarm_optimizer(problem, optimizer::SwarmOptions::default()),
+    Optimizer::Assign | Optimizer::Anneal => optimizer::assignment::assignment_optimizer(problem, 0)?,
+    Optimizer::Grid => optimizer::grid::grid_optimizer(problem, Packing::Hex)?,
+    Optimizer::Perimeter => optimizer::perimeter::perimeter_optimizer(problem)?,
+    Optimizer::Z3 => optimizer::z3::optimize(problem, Z3Options { timeout_ms: Some(60_000), bins: 8 })
+      .ok_or_else(|| anyhow::anyhow!("z3 found no placement"))?,
+  };
+
+  let mut solution = optimizer::to_solution(&placements);
+
+  if optimizer == Optimizer::Anneal {
+    solution = optimizer::annealing::anneal(problem, &solution, AnnealOptions {
+      iterations: 10_000,
+      start_temperature: 100_000.0,
+      end_temperature: 1.0,
+      schedule: Schedule::Exponential,
+    });
+  } else {
+    optimizer::assign_volumes(problem, &mut solution);
+  }
+
+  validation::validate(problem, &solution)?;
+
+  Ok(solution)
+}
+
+/// Every `problem-*.json` in a directory, or every problem file matching a glob pattern
+pub fn problem_files(input: &str) -> Result<Vec<PathBuf>, anyhow::Error> {
+  let mut paths: Vec<PathBuf> = if Path::new(input).is_dir() {
+    std::fs::read_dir(input)?
+      .map(|entry| entry.map(|e| e.path()))
+      .collect::<Result<_, _>>()?
+  } else {
+    glob::glob(input)?.collect::<Result<_, _>>()?
+  };
+
+  paths.retain(|path| problem_id(path).is_some());
+  paths.sort_by_key(|path| problem_id(path));
+
+  Ok(paths)
+}
+
+fn run_one(path: &Path, optimizer: Optimizer, store: &SolutionStore, pictures: Option<(&Path, ImageFormat)>) -> BatchResult {
+  let start = Instant::now();
+  let problem_id = problem_id(path).unwrap();
+
+  let problem_spec: Result<ProblemSpec, anyhow::Error> = std::fs::read_to_string(path)
+    .map_err(anyhow::Error::from)
+    .and_then(|json| Ok(serde_json::from_str(&json)?));
+
+  let (musicians, attendees) = problem_spec.as_ref()
+    .map_or((0, 0), |problem| (problem.musicians.len(), problem.attendees.len()));
+
+  let outcome = problem_spec.and_then(|problem| {
+    let solution = solve(&problem, optimizer)?;
+    let score = scoring::score(&problem, &solution).total;
+
+    let parameters = serde_json::json!({ "batch": format!("{:?}", optimizer) });
+    let record = Record::new(problem_id, score, format!("{:?}", optimizer).to_lowercase(), parameters, solution);
+    let promoted = store.record(&record)
+      .with_context(|| format!("recording problem {}", problem_id))?;
+
+    // the solution is already stored, so a picture that can't be drawn doesn't fail the problem
+    if let Some((dir, format)) = pictures {
+      let picture = dir.join(format!("problem-{}.{}", problem_id, format.extension()));
+
+      if let Err(err) = export::export(&picture, &problem, &record.solution.placements, 2000) {
+        eprintln!("Drawing {} failed: {:#}", picture.display(), err);
+      }
+    }
+
+    Ok((score, promoted))
+  });
+
+  let promoted = outcome.as_ref().is_ok_and(|&(_, promoted)| promoted);
+  let score = outcome.map(|(score, _)| score);
+
+  BatchResult {
+    problem_id,
+    musicians,
+    attendees,
+    score,
+    promoted,
+    runtime: start.elapsed(),
+  }
+}
+
+/// Solves every problem on `threads` worker threads, recording each solution in the store
+/// and drawing it into the pictures directory when given
+pub fn run(
+  paths: Vec<PathBuf>,
+  optimizer: Optimizer,
+  threads: usize,
+  store: &SolutionStore,
+  pictures: Option<(&Path, ImageFormat)>,
+) -> Vec<BatchResult> {
+  let overall = progress::bar(paths.len() as u64);
+  overall.set_message(format!("{:?} batch", optimizer));
+
+  let (work_tx, work_rx) = crossbeam::channel::unbounded::<PathBuf>();
+  let (result_tx, result_rx) = crossbeam::channel::unbounded::<BatchResult>();
+
+  for path in paths {
+    work_tx.send(path).unwrap();
+  }
+  drop(work_tx);
+
+  crossbeam::scope(|scope| {
+    for _ in 0..threads.max(1) {
+      let work_rx = work_rx.clone();
+      let result_tx = result_tx.clone();
+      let overall = &overall;
+
+      scope.spawn(move |_| {
+        for path in work_rx.iter() {
+          let result = run_one(&path, optimizer, store, pictures);
+          overall.inc(1);
+          result_tx.send(result).unwrap();
+        }
+      });
+    }
+  }).unwrap();
+
+  drop(result_tx);
+  overall.finish_with_message(format!("{:?} batch done", optimizer));
+
+  let mut results: Vec<BatchResult> = result_rx.iter().collect();
+  results.sort_by_key(|result| result.problem_id);
+  results
+}
+
+pub fn summary(results: &[BatchResult]) -> String {
+  let mut table = format!("{:>7} {:>9} {:>9} {:>16} {:>10} {:>8}\n", "problem", "musicians", "attendees", "score", "runtime", "new best");
+
+  for result in results {
+    let score = match &result.score {
+      Ok(score) => score.to_string(),
+      Err(err) => format!("error: {}", err),
+    };
+
+    table.push_str(&format!("{:>7} {:>9} {:>9} {:>16} {:>9.1}s {:>8}\n",
+      result.problem_id, result.musicians, result.attendees, score, result.runtime.as_secs_f64(),
+      if result.promoted { "yes" } else { "" }));
+  }
+
+  let total: i64 = results.iter().filter_map(|result| result.score.as_ref().ok()).sum();
+  table.push_str(&format!("{:>7} {:>9} {:>9} {:>16}\n", "total", "", "", total));
+
+  table
+}
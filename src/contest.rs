@@ -0,0 +1,507 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use anyhow::Context;
+use reqwest::StatusCode;
+use reqwest::blocking::{Client, RequestBuilder, Response};
+use reqwest::header::{HeaderMap, HeaderValue};
+use serde::{Deserialize, Serialize};
+use crate::models::{ProblemSpec, Solution};
+use crate::{progress, scoring, validation};
+
+pub const API_URL: &str = "https://api.icfpcontest.com";
+
+/// Every API response is wrapped in one of these
+#[derive(Deserialize)]
+enum ApiResponse<T> {
+  Success(T),
+  Failure(String),
+}
+
+impl<T> ApiResponse<T> {
+  fn into_result(self) -> Result<T, anyhow::Error> {
+    match self {
+      ApiResponse::Success(value) => Ok(value),
+      ApiResponse::Failure(reason) => Err(anyhow::anyhow!("contest API error: {}", reason)),
+    }
+  }
+}
+
+/// Where the official scorer is with a submission
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Evaluation {
+  Processing,
+  Success(f64),
+  Failure(String),
+}
+
+/// A submission and the official evaluation of it, stored next to the local score
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Submitted {
+  pub id: String,
+  pub evaluation: Evaluation,
+}
+
+#[derive(Deserialize)]
+struct SubmissionInfo {
+  submission: SubmissionDetails,
+}
+
+#[derive(Deserialize)]
+struct SubmissionDetails {
+  score: Evaluation,
+}
+
+#[derive(Deserialize)]
+struct ProblemCount {
+  number_of_problems: u32,
+}
+
+/// Refuses solutions that break a placement constraint, printing every violation
+fn check(problem: &ProblemSpec, solution: &Solution) -> Result<(), anyhow::Error> {
+  if let Err(invalid) = validation::validate(problem, solution) {
+    for violation in invalid.0.iter() {
+      eprintln!("{}", violation);
+    }
+
+    return Err(invalid.into());
+  }
+
+  Ok(())
+}
+
+/// Everything the tools need from the contest server
+pub trait ContestApi {
+  /// Validates and submits a solution, returning the submission id
+  fn submit(&self, problem_id: u32, problem: &ProblemSpec, solution: &Solution) -> Result<String, anyhow::Error>;
+
+  fn status(&self, submission_id: &str) -> Result<Evaluation, anyhow::Error>;
+
+  fn problem_count(&self) -> Result<u32, anyhow::Error>;
+
+  /// Raw json of a problem as served
+  fn problem_json(&self, problem_id: u32) -> Result<String, anyhow::Error>;
+
+  /// Raw json of a problem, checked to deserialize into a [ProblemSpec]
+  fn problem(&self, problem_id: u32) -> Result<String, anyhow::Error> {
+    let json = self.problem_json(problem_id)?;
+
+    serde_json::from_str::<ProblemSpec>(&json)
+      .with_context(|| format!("problem {} is not a valid problem spec", problem_id))?;
+
+    Ok(json)
+  }
+
+  /// Polls a submission every `poll_interval` until the scorer is done with it, or gives up after `timeout`
+  fn wait(&self, submission_id: &str, poll_interval: Duration, timeout: Duration) -> Result<Evaluation, anyhow::Error> {
+    let start = Instant::now();
+
+    loop {
+      let evaluation = self.status(submission_id)?;
+
+      if evaluation != Evaluation::Processing {
+        return Ok(evaluation)
+      }
+
+      if start.elapsed() + poll_interval > timeout {
+        anyhow::bail!("submission {} still processing after {:?}", submission_id, timeout)
+      }
+
+      std::thread::sleep(poll_interval);
+    }
+  }
+
+  /// Downloads every `problem-N.json` missing from `dir`, returning the ids that were fetched
+  fn fetch_missing(&self, dir: &Path) -> Result<Vec<u32>, anyhow::Error> {
+    std::fs::create_dir_all(dir)?;
+
+    let missing: Vec<u32> = (1..=self.problem_count()?)
+      .filter(|problem_id| !dir.join(format!("problem-{}.json", problem_id)).exists())
+      .collect();
+
+    let pb = progress::bar(missing.len() as u64);
+    pb.set_message("Fetching problems");
+
+    for &problem_id in missing.iter() {
+      let json = self.problem(problem_id)?;
+      std::fs::write(dir.join(format!("problem-{}.json", problem_id)), json)?;
+      pb.inc(1);
+    }
+
+    pb.finish_with_message(format!("Fetched {} problems", missing.len()));
+
+    Ok(missing)
+  }
+}
+
+/// The contest HTTP API. Requests that get a 429 or 5xx are retried with exponential backoff.
+pub struct ContestClient {
+  client: Client,
+  base_url: String,
+  retries: u32,
+  backoff: Duration,
+}
+
+impl ContestClient {
+  pub fn new(base_url: impl Into<String>, token: &str) -> Result<Self, anyhow::Error> {
+    let mut header_map = HeaderMap::new();
+    header_map.insert("Authorization", HeaderValue::from_str(&format!("Bearer {}", token))?);
+
+    let client = Client::builder()
+      .default_headers(header_map)
+      .build()?;
+
+    Ok(ContestClient {
+      client,
+      base_url: base_url.into().trim_end_matches('/').to_string(),
+      retries: 4,
+      backoff: Duration::from_millis(500),
+    })
+  }
+
+  /// Sends an idempotent request, retrying 429s and 5xx with exponential backoff
+  fn get(&self, request: RequestBuilder) -> Result<Response, anyhow::Error> {
+    let mut delay = self.backoff;
+    let mut attempt = 0;
+
+    loop {
+      let response = request.try_clone()
+        .context("request can't be retried")?
+        .send()?;
+
+      let status = response.status();
+      let retryable = status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error();
+
+      if !retryable || attempt == self.retries {
+        return Ok(response.error_for_status()?)
+      }
+
+      eprintln!("{} from {}, retrying in {:?}", status, response.url(), delay);
+      std::thread::sleep(delay);
+
+      delay *= 2;
+      attempt += 1;
+    }
+  }
+}
+
+impl ContestApi for ContestClient {
+  fn submit(&self, problem_id: u32, problem: &ProblemSpec, solution: &Solution) -> Result<String, anyhow::Error> {
+    check(problem, solution)?;
+
+    #[derive(Serialize)]
+    struct Payload {
+      problem_id: u32,
+      contents: String
+    }
+
+    // never retried, a 5xx may come back after the submission was stored
+    let submission_id: String = self.client.post(format!("{}/submission", self.base_url))
+      .json(&Payload {
+        problem_id,
+        contents: serde_json::to_string(&solution)?
+      })
+      .send()?
+      .error_for_status()?
+      .json()?;
+
+    Ok(submission_id)
+  }
+
+  fn status(&self, submission_id: &str) -> Result<Evaluation, anyhow::Error> {
+    let response: ApiResponse<SubmissionInfo> = self.get(self.client.get(format!("{}/submission", self.base_url))
+      .query(&[("submission_id", submission_id)]))?
+      .json()?;
+
+    Ok(response.into_result()?.submission.score)
+  }
+
+  fn problem_count(&self) -> Result<u32, anyhow::Error> {
+    let count: ProblemCount = self.get(self.client.get(format!("{}/problems", self.base_url)))?
+      .json()?;
+
+    Ok(count.number_of_problems)
+  }
+
+  fn problem_json(&self, problem_id: u32) -> Result<String, anyhow::Error> {
+    let response: ApiResponse<String> = self.get(self.client.get(format!("{}/problem", self.base_url))
+      .query(&[("problem_id", problem_id)]))?
+      .json()?;
+
+    response.into_result()
+  }
+}
+
+/// A submission as recorded by [FakeContest]
+#[derive(Serialize, Deserialize)]
+struct FakeSubmission {
+  problem_id: u32,
+  evaluation: Evaluation,
+  solution: Solution,
+}
+
+/// Offline stand-in for the contest that scores submissions locally and records them as
+/// `<dir>/fake-<n>.json`. Problems are served from `<dir>/problems`.
+pub struct FakeContest {
+  dir: PathBuf,
+}
+
+impl FakeContest {
+  pub fn new(dir: impl Into<PathBuf>) -> Self {
+    FakeContest { dir: dir.into() }
+  }
+
+  fn submission_path(&self, submission_id: &str) -> PathBuf {
+    self.dir.join(format!("{}.json", submission_id))
+  }
+
+  fn problems_dir(&self) -> PathBuf {
+    self.dir.join("problems")
+  }
+}
+
+impl ContestApi for FakeContest {
+  fn submit(&self, problem_id: u32, problem: &ProblemSpec, solution: &Solution) -> Result<String, anyhow::Error> {
+    check(problem, solution)?;
+    std::fs::create_dir_all(&self.dir)?;
+
+    let mut n = 1;
+
+    while self.submission_path(&format!("fake-{}", n)).exists() {
+      n += 1;
+    }
+
+    let submission_id = format!("fake-{}", n);
+
+    let submission = FakeSubmission {
+      problem_id,
+      evaluation: Evaluation::Success(scoring::score(problem, solution).total as f64),
+      solution: solution.clone(),
+    };
+
+    std::fs::write(self.submission_path(&submission_id), serde_json::to_vec(&submission)?)?;
+
+    Ok(submission_id)
+  }
+
+  fn status(&self, submission_id: &str) -> Result<Evaluation, anyhow::Error> {
+    let path = self.submission_path(submission_id);
+    let json = std::fs::read_to_string(&path)
+      .with_context(|| format!("no fake submission {}", submission_id))?;
+
+    let submission: FakeSubmission = serde_json::from_str(&json)?;
+
+    Ok(submission.evaluation)
+  }
+
+  fn problem_count(&self) -> Result<u32, anyhow::Error> {
+    let dir = self.problems_dir();
+
+    if !dir.exists() {
+      return Ok(0)
+    }
+
+    let mut count = 0;
+
+    for entry in std::fs::read_dir(dir)? {
+      if let Some(problem_id) = crate::store::problem_id(&entry?.path()) {
+        count = count.max(problem_id);
+      }
+    }
+
+    Ok(count)
+  }
+
+  fn problem_json(&self, problem_id: u32) -> Result<String, anyhow::Error> {
+    let path = self.problems_dir().join(format!("problem-{}.json", problem_id));
+
+    std::fs::read_to_string(&path)
+      .with_context(|| format!("reading {}", path.display()))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use mockito::Matcher;
+  use super::*;
+
+  const PROBLEM_JSON: &str = include_str!("../problems/example.json");
+  const SOLUTION_JSON: &str = r#"{"placements": [{"x": 590.0, "y": 10.0}, {"x": 1100.0, "y": 100.0}, {"x": 1100.0, "y": 150.0}]}"#;
+
+  fn status_body(score: &str) -> String {
+    format!(r#"{{"Success": {{"submission": {{"_id": "abc", "problem_id": 1, "user_id": "u", "score": {}, "submitted_at": "now"}}, "contents": ""}}}}"#, score)
+  }
+
+  fn client(server: &mockito::Server) -> ContestClient {
+    let mut client = ContestClient::new(server.url(), "token").unwrap();
+    client.backoff = Duration::from_millis(1);
+    client
+  }
+
+  fn example() -> (ProblemSpec, Solution) {
+    (serde_json::from_str(PROBLEM_JSON).unwrap(), serde_json::from_str(SOLUTION_JSON).unwrap())
+  }
+
+  #[test]
+  fn submits_and_polls_until_scored() {
+    let mut server = mockito::Server::new();
+
+    let submit = server.mock("POST", "/submission")
+      .match_header("authorization", "Bearer token")
+      .match_body(Matcher::PartialJsonString(r#"{"problem_id": 1}"#.to_string()))
+      .with_body(r#""abc""#)
+      .create();
+
+    let processing = server.mock("GET", "/submission")
+      .match_query(Matcher::UrlEncoded("submission_id".into(), "abc".into()))
+      .with_body(status_body(r#""Processing""#))
+      .expect(2)
+      .create();
+
+    let scored = server.mock("GET", "/submission")
+      .match_query(Matcher::UrlEncoded("submission_id".into(), "abc".into()))
+      .with_body(status_body(r#"{"Success": 5343.0}"#))
+      .create();
+
+    let contest = client(&server);
+    let (problem, solution) = example();
+
+    let id = contest.submit(1, &problem, &solution).unwrap();
+    assert_eq!(id, "abc");
+    assert_eq!(contest.wait(&id, Duration::from_millis(1), Duration::from_secs(10)).unwrap(), Evaluation::Success(5343.0));
+
+    submit.assert();
+    processing.assert();
+    scored.assert();
+  }
+
+  #[test]
+  fn reports_failures() {
+    let mut server = mockito::Server::new();
+
+    server.mock("GET", "/submission")
+      .match_query(Matcher::UrlEncoded("submission_id".into(), "bad".into()))
+      .with_body(status_body(r#"{"Failure": "musicians overlap"}"#))
+      .create();
+
+    server.mock("GET", "/submission")
+      .match_query(Matcher::UrlEncoded("submission_id".into(), "missing".into()))
+      .with_body(r#"{"Failure": "no such submission"}"#)
+      .create();
+
+    let contest = client(&server);
+
+    assert_eq!(contest.status("bad").unwrap(), Evaluation::Failure("musicians overlap".into()));
+    assert!(contest.status("missing").is_err());
+  }
+
+  #[test]
+  fn retries_throttled_and_failed_requests() {
+    let mut server = mockito::Server::new();
+
+    let throttled = server.mock("GET", "/problems")
+      .with_status(429)
+      .expect(1)
+      .create();
+
+    let unavailable = server.mock("GET", "/problems")
+      .with_status(503)
+      .expect(1)
+      .create();
+
+    let count = server.mock("GET", "/problems")
+      .with_body(r#"{"number_of_problems": 90}"#)
+      .create();
+
+    assert_eq!(client(&server).problem_count().unwrap(), 90);
+
+    throttled.assert();
+    unavailable.assert();
+    count.assert();
+  }
+
+  #[test]
+  fn never_retries_submissions() {
+    let mut server = mockito::Server::new();
+
+    let unavailable = server.mock("POST", "/submission")
+      .with_status(503)
+      .expect(1)
+      .create();
+
+    let (problem, solution) = example();
+    assert!(client(&server).submit(1, &problem, &solution).is_err());
+
+    unavailable.assert();
+  }
+
+  #[test]
+  fn gives_up_after_retries() {
+    let mut server = mockito::Server::new();
+
+    let unavailable = server.mock("GET", "/problems")
+      .with_status(500)
+      .expect(5)
+      .create();
+
+    assert!(client(&server).problem_count().is_err());
+
+    unavailable.assert();
+  }
+
+  #[test]
+  fn fetches_missing_problems() {
+    let mut server = mockito::Server::new();
+
+    server.mock("GET", "/problems")
+      .with_body(r#"{"number_of_problems": 3}"#)
+      .create();
+
+    let mut problem = |id: &str, hits: usize| {
+      server.mock("GET", "/problem")
+        .match_query(Matcher::UrlEncoded("problem_id".into(), id.into()))
+        .with_body(serde_json::json!({ "Success": PROBLEM_JSON }).to_string())
+        .expect(hits)
+        .create()
+    };
+
+    let first = problem("1", 0);
+    let second = problem("2", 1);
+    let third = problem("3", 1);
+
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("problem-1.json"), PROBLEM_JSON).unwrap();
+
+    assert_eq!(client(&server).fetch_missing(dir.path()).unwrap(), vec![2, 3]);
+    assert!(serde_json::from_str::<ProblemSpec>(&std::fs::read_to_string(dir.path().join("problem-3.json")).unwrap()).is_ok());
+
+    first.assert();
+    second.assert();
+    third.assert();
+  }
+
+  #[test]
+  fn rejects_invalid_problems() {
+    let mut server = mockito::Server::new();
+
+    server.mock("GET", "/problem")
+      .match_query(Matcher::UrlEncoded("problem_id".into(), "4".into()))
+      .with_body(r#"{"Success": "{\"room_width\": 10.0}"}"#)
+      .create();
+
+    assert!(client(&server).problem(4).is_err());
+  }
+
+  #[test]
+  fn fake_records_submissions() {
+    let dir = tempfile::tempdir().unwrap();
+    let contest = FakeContest::new(dir.path());
+    let (problem, solution) = example();
+
+    let first = contest.submit(1, &problem, &solution).unwrap();
+    let second = contest.submit(1, &problem, &solution).unwrap();
+
+    assert_ne!(first, second);
+    assert_eq!(contest.wait(&second, Duration::from_millis(1), Duration::from_secs(1)).unwrap(), Evaluation::Success(5343.0));
+    assert!(dir.path().join(format!("{}.json", first)).exists());
+  }
+}
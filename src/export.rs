@@ -0,0 +1,200 @@
+use std::path::Path;
+use colorous::Color;
+use image::{Rgb, RgbImage};
+use crate::models::{Position, ProblemSpec};
+
+const BACKGROUND: Color = Color { r: 128, g: 128, b: 128 };
+const ROOM: Color = Color { r: 0, g: 0, b: 0 };
+const STAGE: Color = Color { r: 50, g: 205, b: 50 };
+const ATTENDEE: Color = Color { r: 255, g: 0, b: 0 };
+const PILLAR: Color = Color { r: 64, g: 64, b: 64 };
+
+/// Radius musicians are drawn with, matching the renderer
+const MUSICIAN_SIZE: f32 = 5.0;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum ImageFormat {
+  Svg,
+  Png,
+}
+
+impl ImageFormat {
+  pub fn extension(self) -> &'static str {
+    match self {
+      ImageFormat::Svg => "svg",
+      ImageFormat::Png => "png",
+    }
+  }
+}
+
+/// Everything is drawn as one of these, in room coordinates with y pointing up
+enum Shape {
+  Rect { bottom_left: Position, width: f32, height: f32, color: Color },
+  Circle { center: Position, radius: f32, color: Color },
+}
+
+/// The room, stage, pillars, attendees and musicians coloured by instrument as in [crate::render], back to front
+fn scene(problem: &ProblemSpec, placements: &[Position]) -> Vec<Shape> {
+  let max_inst = problem.musicians.iter().max().map_or(1, |inst| inst.0 + 1);
+
+  let mut shapes = vec![
+    Shape::Rect {
+      bottom_left: Position { x: 0.0, y: 0.0 },
+      width: problem.room_width,
+      height: problem.room_height,
+      color: ROOM,
+    },
+    Shape::Rect {
+      bottom_left: Position { x: problem.stage_bottom_left[0], y: problem.stage_bottom_left[1] },
+      width: problem.stage_width,
+      height: problem.stage_height,
+      color: STAGE,
+    },
+  ];
+
+  shapes.extend(problem.pillars.iter().map(|pillar| Shape::Circle {
+    center: pillar.position(),
+    radius: pillar.radius,
+    color: PILLAR,
+  }));
+
+  shapes.extend(problem.attendees.iter().map(|attendee| Shape::Circle {
+    center: attendee.position,
+    radius: 1.0,
+    color: ATTENDEE,
+  }));
+
+  shapes.extend(placements.iter().zip(problem.musicians.iter()).map(|(&center, inst)| Shape::Circle {
+    center,
+    radius: MUSICIAN_SIZE,
+    color: colorous::RAINBOW.eval_rational(inst.0, max_inst),
+  }));
+
+  shapes
+}
+
+pub fn svg(problem: &ProblemSpec, placements: &[Position]) -> String {
+  let mut svg = format!(
+    "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 {w} {h}\" width=\"{w}\" height=\"{h}\">\n\
+     <rect width=\"100%\" height=\"100%\" fill=\"#{:x}\"/>\n\
+     <g transform=\"translate(0 {h}) scale(1 -1)\">\n",
+    BACKGROUND, w = problem.room_width, h = problem.room_height);
+
+  for shape in scene(problem, placements) {
+    match shape {
+      Shape::Rect { bottom_left, width, height, color } => svg.push_str(&format!(
+        "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"#{:x}\"/>\n",
+        bottom_left.x, bottom_left.y, width, height, color)),
+      Shape::Circle { center, radius, color } => svg.push_str(&format!(
+        "<circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"#{:x}\"/>\n",
+        center.x, center.y, radius, color)),
+    }
+  }
+
+  svg.push_str("</g>\n</svg>\n");
+  svg
+}
+
+/// Rasterizes the scene so the longest room side is `size` pixels. Shapes are drawn at least one pixel wide.
+pub fn png(problem: &ProblemSpec, placements: &[Position], size: u32) -> RgbImage {
+  let scale = size as f32 / problem.room_width.max(problem.room_height);
+  let width = ((problem.room_width * scale).ceil() as u32).max(1);
+  let height = ((problem.room_height * scale).ceil() as u32).max(1);
+
+  let rgb = |color: Color| Rgb([color.r, color.g, color.b]);
+  let mut image = RgbImage::from_pixel(width, height, rgb(BACKGROUND));
+
+  // pixel bounds covering a room space box, flipped so y points down
+  let pixels = |min: Position, max: Position| {
+    let x0 = ((min.x * scale).floor().max(0.0) as u32).min(width - 1);
+    let x1 = ((max.x * scale).ceil().max(0.0) as u32).min(width);
+    let y0 = (((problem.room_height - max.y) * scale).floor().max(0.0) as u32).min(height - 1);
+    let y1 = (((problem.room_height - min.y) * scale).ceil().max(0.0) as u32).min(height);
+
+    (x0..x1.max(x0 + 1)).flat_map(move |x| (y0..y1.max(y0 + 1)).map(move |y| (x, y)))
+  };
+
+  for shape in scene(problem, placements) {
+    match shape {
+      Shape::Rect { bottom_left, width, height, color } => {
+        let top_right = Position { x: bottom_left.x + width, y: bottom_left.y + height };
+
+        for (x, y) in pixels(bottom_left, top_right) {
+          image.put_pixel(x, y, rgb(color));
+        }
+      }
+      Shape::Circle { center, radius, color } => {
+        let radius = radius.max(0.5 / scale);
+        let min = Position { x: center.x - radius, y: center.y - radius };
+        let max = Position { x: center.x + radius, y: center.y + radius };
+
+        // circles smaller than a pixel still fill the pixel holding their center
+        let px_radius = (radius * scale).max(0.5);
+        let px_center = (center.x * scale, (problem.room_height - center.y) * scale);
+
+        for (x, y) in pixels(min, max) {
+          let dx = x as f32 + 0.5 - px_center.0;
+          let dy = y as f32 + 0.5 - px_center.1;
+
+          if dx * dx + dy * dy <= px_radius * px_radius || px_radius <= 0.5 {
+            image.put_pixel(x, y, rgb(color));
+          }
+        }
+      }
+    }
+  }
+
+  image
+}
+
+/// Writes an SVG or PNG depending on the extension of `path`
+pub fn export(path: &Path, problem: &ProblemSpec, placements: &[Position], size: u32) -> Result<(), anyhow::Error> {
+  match path.extension().and_then(|ext| ext.to_str()) {
+    Some("svg") => std::fs::write(path, svg(problem, placements))?,
+    Some("png") => png(problem, placements, size).save(path)?,
+    _ => anyhow::bail!("{} is neither .svg nor .png", path.display()),
+  }
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const PROBLEM_JSON: &str = include_str!("../problems/example.json");
+
+  fn placements() -> Vec<Position> {
+    vec![
+      Position { x: 590.0, y: 10.0 },
+      Position { x: 1100.0, y: 100.0 },
+      Position { x: 1100.0, y: 150.0 },
+    ]
+  }
+
+  #[test]
+  fn draws_every_musician() {
+    let problem: ProblemSpec = serde_json::from_str(PROBLEM_JSON).unwrap();
+    let svg = svg(&problem, &placements());
+
+    // three attendees and three musicians
+    assert_eq!(svg.matches("<circle").count(), 6);
+    assert!(svg.contains("cx=\"1100\" cy=\"150\" r=\"5\""));
+  }
+
+  #[test]
+  fn rasterizes_room_coordinates() {
+    let problem: ProblemSpec = serde_json::from_str(PROBLEM_JSON).unwrap();
+    let image = png(&problem, &placements(), 500);
+
+    assert_eq!(image.dimensions(), (200, 500));
+
+    // room is 2000x5000 so a pixel is 10 units, with y flipped
+    let rgb = |color: Color| Rgb([color.r, color.g, color.b]);
+
+    assert_eq!(*image.get_pixel(100, 100), rgb(ROOM));
+    assert_eq!(*image.get_pixel(60, 490), rgb(STAGE));
+    assert_eq!(*image.get_pixel(10, 450), rgb(ATTENDEE));
+    assert_eq!(*image.get_pixel(110, 484), rgb(colorous::RAINBOW.eval_rational(0, 2)));
+  }
+}
@@ -1,14 +1,24 @@
-use std::path::{PathBuf};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use crate::models::{Position, ProblemSpec, Solution};
 use clap::{Parser, Subcommand};
-use reqwest::header::{HeaderMap, HeaderValue};
-use reqwest::blocking::Response;
-use serde::Serialize;
 use crate::optimizer::MusicianId;
+use crate::optimizer::annealing::{AnnealOptions, Schedule};
+use crate::optimizer::grid::Packing;
+use crate::store::{Record, SolutionStore};
+use crate::contest::{ContestApi, ContestClient, Evaluation, FakeContest, Submitted};
 
+mod batch;
+mod contest;
+mod export;
 mod models;
 mod render;
 mod optimizer;
+mod progress;
+mod scoring;
+mod store;
+mod validation;
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -16,6 +26,24 @@ mod optimizer;
 struct Cli {
   #[command(subcommand)]
   command: Commands,
+  /// Directory every produced solution is recorded in
+  #[arg(long, global = true, env = "SOLUTION_STORE", default_value = "solutions")]
+  store: PathBuf,
+  /// Base URL of the contest API
+  #[arg(long, global = true, env = "API_URL", default_value = contest::API_URL)]
+  api_url: String,
+  /// Record submissions in this directory and score them locally instead of using the contest API
+  #[arg(long, global = true, env = "FAKE_CONTEST")]
+  fake_contest: Option<PathBuf>,
+}
+
+impl Cli {
+  fn contest(&self) -> Result<Box<dyn ContestApi>, anyhow::Error> {
+    match &self.fake_contest {
+      Some(dir) => Ok(Box::new(FakeContest::new(dir))),
+      None => Ok(Box::new(ContestClient::new(&self.api_url, &std::env::var("API_TOKEN")?)?)),
+    }
+  }
 }
 
 #[derive(Subcommand)]
@@ -23,12 +51,34 @@ enum Commands {
   /// Runs optimizer on provided problem
   Optimize {
     problem: PathBuf,
+    /// Stop searching after this many seconds and use the best placement found so far
+    #[arg(short, long)]
+    timeout: Option<u64>,
+    /// Bins along each stage axis used to approximate the score
+    #[arg(short, long, default_value_t = 8)]
+    bins: usize,
   },
   /// Runs Renderer on provided problem
   Render {
     problem: PathBuf,
     #[arg(short, long)]
-    solution: Option<PathBuf>
+    solution: Option<PathBuf>,
+    /// Another solution of the same problem to compare the shown one against
+    #[arg(short, long, requires = "solution")]
+    baseline: Option<PathBuf>,
+    /// Where S saves the layout after dragging musicians around
+    #[arg(long, default_value = "solution-edited.json")]
+    save: PathBuf,
+  },
+  /// Draws a problem and optionally a solution to an .svg or .png file without opening a window
+  Export {
+    problem: PathBuf,
+    output: PathBuf,
+    #[arg(short, long)]
+    solution: Option<PathBuf>,
+    /// Pixels along the longest side of the room for png output
+    #[arg(long, default_value_t = 2000)]
+    size: u32,
   },
   /// Runs Particle Swarm Optimizer on the provided problem
   Swarm {
@@ -37,97 +87,494 @@ enum Commands {
     render: bool,
     #[arg(short, long)]
     submit: Option<u32>,
+    /// Seconds to wait for the official score of a submission
+    #[arg(long, default_value_t = 300)]
+    submit_timeout: u64,
+    /// Extra passes re-optimizing each musician against every other musician
+    #[arg(long, default_value_t = 0)]
+    sweeps: usize,
+    /// Refine the result with a PSO over all musicians at once
+    #[arg(long)]
+    joint: bool,
+    /// Start from the perimeter-first layout, best combined with --sweeps
+    #[arg(long)]
+    perimeter_seed: bool,
+  },
+  /// Assigns musicians to grid slots by solving the instrument/slot assignment problem
+  Assign {
+    problem: PathBuf,
+    /// Rounds of rescoring slots with the previous layout's musicians as occluders
+    #[arg(short, long, default_value_t = 0)]
+    blocking_rounds: usize,
+    #[arg(short, long)]
+    render: bool,
+  },
+  /// Assigns musicians to the slots of a square, hex or perimeter-first grid
+  Grid {
+    problem: PathBuf,
+    #[arg(short, long, value_enum, default_value_t = Packing::Hex)]
+    packing: Packing,
+    #[arg(short, long)]
+    render: bool,
+  },
+  /// Fills the stage edges facing the most audience first, inner rings only take the leftover musicians
+  Perimeter {
+    problem: PathBuf,
+    #[arg(short, long)]
+    render: bool,
+  },
+  /// Improves a solution with simulated annealing
+  Anneal {
+    problem: PathBuf,
+    /// Solution to start from, runs the particle swarm optimizer when missing
+    #[arg(short, long)]
+    solution: Option<PathBuf>,
+    #[arg(short, long, default_value_t = 10_000)]
+    iterations: usize,
+    #[arg(long, default_value_t = 100_000.0)]
+    start_temperature: f64,
+    #[arg(long, default_value_t = 1.0)]
+    end_temperature: f64,
+    #[arg(long, value_enum, default_value_t = Schedule::Exponential)]
+    schedule: Schedule,
+  },
+  /// Runs an optimizer on every problem in a directory or glob in parallel
+  Batch {
+    /// Directory holding problem-*.json files, or a glob pattern
+    input: String,
+    #[arg(short, long, value_enum, default_value_t = batch::Optimizer::Assign)]
+    optimizer: batch::Optimizer,
+    /// Worker threads, defaults to the number of cores
+    #[arg(short, long)]
+    threads: Option<usize>,
+    /// Directory a picture of every solution is written to
+    #[arg(long)]
+    pictures: Option<PathBuf>,
+    #[arg(long, value_enum, default_value_t = export::ImageFormat::Png)]
+    picture_format: export::ImageFormat,
+  },
+  /// Lists the best stored solution of every problem
+  Best,
+  /// Submits every stored best solution that beats the last accepted submission of its problem
+  SubmitBest {
+    /// Only list what would be submitted
+    #[arg(long)]
+    dry_run: bool,
+    /// Directory holding the problem-*.json files the solutions are validated against
+    #[arg(long, default_value = "problems")]
+    problems: PathBuf,
+    /// Seconds to wait for the official score of each submission
+    #[arg(long, default_value_t = 300)]
+    submit_timeout: u64,
+    /// Resubmit bests whose last submission was rejected
+    #[arg(long)]
+    retry_failed: bool,
+  },
+  /// Downloads every problem missing from a directory from the contest API
+  Fetch {
+    #[arg(default_value = "problems")]
+    dir: PathBuf,
+  },
+  /// Computes the exact score of a solution for the provided problem
+  Score {
+    problem: PathBuf,
+    solution: PathBuf,
+    /// Ignore the full-round extensions (playing together) even if the problem has pillars
+    #[arg(short, long)]
+    lightning: bool,
+  },
+  /// Checks a solution against every placement constraint of the provided problem
+  Validate {
+    problem: PathBuf,
+    solution: PathBuf,
+  },
+  /// Sets each musician in a solution to full volume or mutes them, whichever scores better
+  Volumes {
+    problem: PathBuf,
+    solution: PathBuf,
   },
 }
 
 fn main() -> Result<(), anyhow::Error> {
   dotenvy::dotenv()?;
   let cli: Cli = Cli::parse();
+  let store = SolutionStore::open(&cli.store);
 
   match &cli.command {
-    Commands::Optimize { problem } => {
+    Commands::Optimize { problem, timeout, bins } => {
       let json = std::fs::read_to_string(problem)?;
       let problem_spec: ProblemSpec = serde_json::from_str(&json)?;
-      dbg!(optimizer::z3::optimize(problem_spec));
+      let result = optimizer::z3::optimize(&problem_spec, optimizer::z3::Z3Options {
+        timeout_ms: timeout.map(|secs| secs * 1000),
+        bins: *bins,
+      }).ok_or_else(|| anyhow::anyhow!("z3 found no placement"))?;
+
+      let mut solution = optimizer::to_solution(&result);
+      optimizer::assign_volumes(&problem_spec, &mut solution);
+
+      validation::validate(&problem_spec, &solution)?;
+      let score = scoring::score(&problem_spec, &solution).total;
+      println!("Score: {}", score);
+
+      save(&store, problem, &solution, score, "z3", serde_json::json!({ "timeout": timeout, "bins": bins }), None)?;
     }
-    Commands::Render { problem, solution } => {
+    Commands::Render { problem, solution, baseline, save } => {
       let json = std::fs::read_to_string(problem)?;
       let problem_spec: ProblemSpec = serde_json::from_str(&json)?;
-      render::run_app(problem_spec, solution.clone().map(|sol| {
-        let json = std::fs::read_to_string(sol).unwrap();
-        let solution: Solution = serde_json::from_str(&json).unwrap();
-        solution.placements.into_iter().enumerate()
-          .map(|(id, p)| (MusicianId(id), p)).collect()
-      } ));
-    }
-    Commands::Swarm { problem, render, submit } => {
+      let load = |path: &PathBuf| -> Result<Solution, anyhow::Error> {
+        let solution: Solution = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        anyhow::ensure!(solution.placements.len() == problem_spec.musicians.len(),
+          "{} places {} musicians, the problem has {}", path.display(), solution.placements.len(), problem_spec.musicians.len());
+        Ok(solution)
+      };
+
+      let solution = solution.as_ref().map(load).transpose()?;
+      let baseline = baseline.as_ref().map(load).transpose()?;
+
+      render::run_app(store::problem_id(problem), problem_spec, solution, baseline, save.clone());
+    }
+    Commands::Export { problem, output, solution, size } => {
       let json = std::fs::read_to_string(problem)?;
       let problem_spec: ProblemSpec = serde_json::from_str(&json)?;
-      let result = optimizer::particle_swarm_optimizer(&problem_spec);
 
-      let mut ordered: Vec<Position> = Vec::with_capacity(result.len());
+      let placements = match solution {
+        Some(path) => serde_json::from_str::<Solution>(&std::fs::read_to_string(path)?)?.placements,
+        None => Vec::new(),
+      };
 
-      for idx in 0..result.len() {
-        let v = result.get(&MusicianId(idx)).unwrap();
-        ordered.push(Position { x: v.x, y: v.y });
-      }
+      export::export(output, &problem_spec, &placements, *size)?;
+    }
+    Commands::Swarm { problem, render, submit, submit_timeout, sweeps, joint, perimeter_seed } => {
+      let json = std::fs::read_to_string(problem)?;
+      let problem_spec: ProblemSpec = serde_json::from_str(&json)?;
+      let options = optimizer::SwarmOptions {
+        sweeps: *sweeps,
+        joint: *joint,
+        perimeter_seed: *perimeter_seed,
+      };
+
+      let finish = |result: &HashMap<MusicianId, Position>| -> Result<i64, anyhow::Error> {
+        let mut solution = optimizer::to_solution(result);
+
+        optimizer::assign_volumes(&problem_spec, &mut solution);
+
+        let score = scoring::score(&problem_spec, &solution).total;
+        println!("Score: {}", score);
 
-      let solution = Solution {
-        placements: ordered
+        // a failed submission shouldn't lose the solution, so it is only reported
+        let submission = submit.and_then(|problem_id| {
+          cli.contest()
+            .and_then(|contest| submit_and_wait(contest.as_ref(), problem_id, &problem_spec, &solution, score, Duration::from_secs(*submit_timeout)))
+            .map_err(|err| eprintln!("Submission failed: {:#}", err))
+            .ok()
+        });
+
+        save(&store, problem, &solution, score, "swarm", serde_json::json!({ "sweeps": sweeps, "joint": joint, "perimeter_seed": perimeter_seed }), submission)?;
+
+        Ok(score)
       };
 
-      std::fs::write(format!("solution-{}", problem.file_name().unwrap().to_str().unwrap()), &serde_json::to_vec(&solution)?)?;
+      if !*render {
+        finish(&optimizer::particle_swarm_optimizer(&problem_spec, options))?;
+        return Ok(())
+      }
+
+      // the window has to own the main thread, so the optimizer streams its progress from a worker.
+      // closing the window exits the process, so keep it open until the solution is saved.
+      let (progress, feed) = crossbeam::channel::unbounded();
 
+      std::thread::scope(|scope| {
+        scope.spawn(|| {
+          let mut last = 0.0;
+          let result = optimizer::particle_swarm_optimizer_with(&problem_spec, options, |snapshot| {
+            last = snapshot.score;
+            // the window may already be closed, nothing to do then
+            let _ = progress.send(snapshot);
+          });
 
-      if let Some(problem_id) = submit {
-        let response = Submitter::submit(*problem_id, &solution)?;
-        println!("Response code: {}", response.status())
+          // the plot stays on the optimizer's objective, the exact score is shown next to it
+          match finish(&result) {
+            Ok(score) => {
+              let _ = progress.send(optimizer::Snapshot { placements: result, score: last, exact: Some(score) });
+            }
+            Err(err) => eprintln!("Saving the solution failed: {:#}", err),
+          }
+
+          drop(progress);
+        });
+
+        render::run_live(store::problem_id(problem), problem_spec.clone(), feed, PathBuf::from("solution-edited.json"));
+      });
+    }
+    Commands::Assign { problem, blocking_rounds, render } => {
+      let json = std::fs::read_to_string(problem)?;
+      let problem_spec: ProblemSpec = serde_json::from_str(&json)?;
+      let result = optimizer::assignment::assignment_optimizer(&problem_spec, *blocking_rounds)?;
+
+      let mut solution = optimizer::to_solution(&result);
+      optimizer::assign_volumes(&problem_spec, &mut solution);
+
+      let score = scoring::score(&problem_spec, &solution).total;
+      println!("Score: {}", score);
+
+      save(&store, problem, &solution, score, "assign", serde_json::json!({ "blocking_rounds": blocking_rounds }), None)?;
+
+      if *render {
+        render::run_app(store::problem_id(problem), problem_spec, Some(solution), None, PathBuf::from("solution-edited.json"))
       }
+    }
+    Commands::Grid { problem, packing, render } => {
+      let json = std::fs::read_to_string(problem)?;
+      let problem_spec: ProblemSpec = serde_json::from_str(&json)?;
+      let result = optimizer::grid::grid_optimizer(&problem_spec, *packing)?;
+
+      let mut solution = optimizer::to_solution(&result);
+      optimizer::assign_volumes(&problem_spec, &mut solution);
+
+      let score = scoring::score(&problem_spec, &solution).total;
+      println!("Score: {}", score);
+
+      save(&store, problem, &solution, score, "grid", serde_json::json!({ "packing": format!("{:?}", packing) }), None)?;
 
       if *render {
-        render::run_app(problem_spec, Some(result))
+        render::run_app(store::problem_id(problem), problem_spec, Some(solution), None, PathBuf::from("solution-edited.json"))
       }
     }
-  }
+    Commands::Perimeter { problem, render } => {
+      let json = std::fs::read_to_string(problem)?;
+      let problem_spec: ProblemSpec = serde_json::from_str(&json)?;
+      let result = optimizer::perimeter::perimeter_optimizer(&problem_spec)?;
 
-  Ok(())
-}
+      let mut solution = optimizer::to_solution(&result);
+      optimizer::assign_volumes(&problem_spec, &mut solution);
+
+      let score = scoring::score(&problem_spec, &solution).total;
+      println!("Score: {}", score);
+
+      save(&store, problem, &solution, score, "perimeter", serde_json::json!({}), None)?;
+
+      if *render {
+        render::run_app(store::problem_id(problem), problem_spec, Some(solution), None, PathBuf::from("solution-edited.json"))
+      }
+    }
+    Commands::Anneal { problem, solution, iterations, start_temperature, end_temperature, schedule } => {
+      let json = std::fs::read_to_string(problem)?;
+      let problem_spec: ProblemSpec = serde_json::from_str(&json)?;
+
+      let start = match solution {
+        Some(path) => {
+          let json = std::fs::read_to_string(path)?;
+          serde_json::from_str(&json)?
+        }
+        None => {
+          let result = optimizer::particle_swarm_optimizer(&problem_spec, optimizer::SwarmOptions::default());
+          optimizer::to_solution(&result)
+        }
+      };
+
+      validation::validate(&problem_spec, &start)?;
+
+      let annealed = optimizer::annealing::anneal(&problem_spec, &start, AnnealOptions {
+        iterations: *iterations,
+        start_temperature: *start_temperature,
+        end_temperature: *end_temperature,
+        schedule: *schedule,
+      });
+
+      // annealed comes back with tuned volumes, so tune the start too and only count what moving musicians gained
+      let mut tuned = start.clone();
+      optimizer::assign_volumes(&problem_spec, &mut tuned);
+
+      let before = scoring::score(&problem_spec, &tuned).total;
+      let after = scoring::score(&problem_spec, &annealed).total;
 
-struct Submitter;
+      println!("Score: {} -> {} ({:+})", before, after, after - before);
 
-impl Submitter {
-  fn submit(problem_id: u32, solution: &Solution) -> Result<Response, anyhow::Error> {
-    let mut header_map = HeaderMap::new();
+      save(&store, problem, &annealed, after, "anneal", serde_json::json!({
+        "start": solution,
+        "iterations": iterations,
+        "start_temperature": start_temperature,
+        "end_temperature": end_temperature,
+        "schedule": format!("{:?}", schedule),
+      }), None)?;
+    }
+    Commands::Batch { input, optimizer, threads, pictures, picture_format } => {
+      let paths = batch::problem_files(input)?;
+      let threads = threads.unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |n| n.get()));
+
+      if let Some(dir) = pictures {
+        std::fs::create_dir_all(dir)?;
+      }
+
+      let pictures = pictures.as_deref().map(|dir| (dir, *picture_format));
+      let results = batch::run(paths, *optimizer, threads, &store, pictures);
+      let summary = batch::summary(&results);
+
+      println!("{}", summary);
+      std::fs::write(store.root().join("summary.txt"), summary)?;
+    }
+    Commands::SubmitBest { dry_run, problems, submit_timeout, retry_failed } => {
+      let contest = if *dry_run { None } else { Some(cli.contest()?) };
+
+      for problem_id in store.problem_ids()? {
+        let Some(best) = store.best(problem_id)? else { continue };
+        let last = store.last_submitted(problem_id)?;
+
+        if last.as_ref().is_some_and(|last| last.score >= best.score) {
+          continue
+        }
+
+        if !*retry_failed && store.rejected(&best)? {
+          println!("Problem {}: skipping {}, its last submission was rejected (--retry-failed to resubmit)", problem_id, best.score);
+          continue
+        }
 
-    let token = std::env::var("API_TOKEN")?;
+        let last_score = last.map_or("none".to_string(), |last| last.score.to_string());
+        println!("Problem {}: {} -> {} ({})", problem_id, last_score, best.score, best.optimizer);
 
-    header_map.insert("Authorization", HeaderValue::from_str(&format!("Bearer {}", token))?);
+        let Some(contest) = &contest else { continue };
 
-    let client = reqwest::blocking::Client::builder()
-      .default_headers(header_map)
-      .build()?;
+        let result = std::fs::read_to_string(problems.join(format!("problem-{}.json", problem_id)))
+          .map_err(anyhow::Error::from)
+          .and_then(|json| Ok(serde_json::from_str::<ProblemSpec>(&json)?))
+          .and_then(|problem_spec| submit_and_wait(contest.as_ref(), problem_id, &problem_spec, &best.solution, best.score,
+            Duration::from_secs(*submit_timeout)));
 
-    #[derive(Serialize)]
-    struct Payload {
-      problem_id: u32,
-      contents: String
+        match result {
+          Ok(submission) => { store.record(&best.submitted(submission))?; }
+          Err(err) => eprintln!("Submitting problem {} failed: {:#}", problem_id, err),
+        }
+      }
+    }
+    Commands::Fetch { dir } => {
+      let fetched = cli.contest()?.fetch_missing(dir)?;
+      println!("Fetched {} problems into {}", fetched.len(), dir.display());
+    }
+    Commands::Best => {
+      println!("{:>7} {:>16} {:>10} {:>14} {:>5}", "problem", "score", "optimizer", "timestamp", "runs");
+
+      for problem_id in store.problem_ids()? {
+        if let Some(best) = store.best(problem_id)? {
+          let runs = store.history(problem_id)?.len();
+          println!("{:>7} {:>16} {:>10} {:>14} {:>5}", problem_id, best.score, best.optimizer, best.timestamp, runs);
+        }
+      }
     }
+    Commands::Score { problem, solution, lightning } => {
+      let json = std::fs::read_to_string(problem)?;
+      let problem_spec: ProblemSpec = serde_json::from_str(&json)?;
+      let json = std::fs::read_to_string(solution)?;
+      let solution: Solution = serde_json::from_str(&json)?;
+
+      let extensions = if *lightning {
+        scoring::Extensions::NONE
+      } else {
+        scoring::Extensions::for_problem(&problem_spec)
+      };
 
-    let response = client.post("https://api.icfpcontest.com/submission")
-      .json(&Payload {
-        problem_id,
-        contents: serde_json::to_string(&solution)?
-      })
-      .send()?;
+      let score = scoring::score_with(&problem_spec, &solution, extensions);
 
-    Ok(response)
+      for (idx, musician_score) in score.musicians.iter().enumerate() {
+        println!("{:?} ({:?}): {}", MusicianId(idx), problem_spec.musicians[idx], musician_score);
+      }
+
+      println!("Score: {}", score.total);
+    }
+    Commands::Validate { problem, solution } => {
+      let json = std::fs::read_to_string(problem)?;
+      let problem_spec: ProblemSpec = serde_json::from_str(&json)?;
+      let json = std::fs::read_to_string(solution)?;
+      let solution: Solution = serde_json::from_str(&json)?;
+
+      let violations = validation::violations(&problem_spec, &solution);
+
+      for violation in violations.iter() {
+        println!("{}", violation);
+      }
+
+      if !violations.is_empty() {
+        return Err(validation::InvalidSolution(violations).into());
+      }
+
+      println!("Solution is valid");
+    }
+    Commands::Volumes { problem, solution: solution_path } => {
+      let json = std::fs::read_to_string(problem)?;
+      let problem_spec: ProblemSpec = serde_json::from_str(&json)?;
+      let json = std::fs::read_to_string(solution_path)?;
+      let mut solution: Solution = serde_json::from_str(&json)?;
+
+      let before = scoring::score(&problem_spec, &solution).total;
+      optimizer::assign_volumes(&problem_spec, &mut solution);
+      let after = scoring::score(&problem_spec, &solution).total;
+
+      println!("Score: {} -> {}", before, after);
+
+      std::fs::write(solution_path, &serde_json::to_vec(&solution)?)?;
+    }
   }
+
+  Ok(())
+}
+
+/// Records a solution in the store, problems without an id in their file name are written to `solution-<file>` instead
+fn save(
+  store: &SolutionStore,
+  problem: &Path,
+  solution: &Solution,
+  score: i64,
+  optimizer: &str,
+  parameters: serde_json::Value,
+  submission: Option<Submitted>,
+) -> Result<(), anyhow::Error> {
+  match store::problem_id(problem) {
+    Some(problem_id) => {
+      let mut record = Record::new(problem_id, score, optimizer, parameters, solution.clone());
+      record.submission = submission;
+
+      if store.record(&record)? {
+        println!("New best for problem {}", problem_id);
+      } else if let Some(best) = store.best(problem_id)? {
+        println!("Stored best for problem {} is still {}", problem_id, best.score);
+      }
+    }
+    None => {
+      std::fs::write(format!("solution-{}", problem.file_name().unwrap().to_str().unwrap()), &serde_json::to_vec(solution)?)?;
+    }
+  }
+
+  Ok(())
+}
+
+/// Submits a solution, waits for the official score and warns when it disagrees with the local one
+fn submit_and_wait(
+  contest: &dyn ContestApi,
+  problem_id: u32,
+  problem: &ProblemSpec,
+  solution: &Solution,
+  local_score: i64,
+  timeout: Duration,
+) -> Result<Submitted, anyhow::Error> {
+  let id = contest.submit(problem_id, problem, solution)?;
+  println!("Submitted {}, waiting for the official score", id);
+
+  let evaluation = contest.wait(&id, Duration::from_secs(5), timeout)?;
+
+  match &evaluation {
+    Evaluation::Success(official) if *official as i64 != local_score =>
+      eprintln!("Scorer mismatch: official score {} but local score {}", official, local_score),
+    Evaluation::Success(official) => println!("Official score: {}", official),
+    Evaluation::Failure(reason) => eprintln!("Submission rejected: {}", reason),
+    Evaluation::Processing => unreachable!("wait only returns finished evaluations"),
+  }
+
+  Ok(Submitted { id, evaluation })
 }
 
 #[cfg(test)]
 mod tests {
   use crate::ProblemSpec;
-  const PROBLEM_JSON: &str = include_str!("problems/example.json");
+  const PROBLEM_JSON: &str = include_str!("../problems/example.json");
 
   #[test]
   fn parse_problem() {
@@ -20,7 +20,21 @@ pub struct ProblemSpec {
   pub stage_width: Dimension,
   pub stage_bottom_left: [Dimension; 2],
   pub musicians: Vec<Instrument>,
-  pub attendees: Vec<Attendee>
+  pub attendees: Vec<Attendee>,
+  #[serde(default)]
+  pub pillars: Vec<Pillar>
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct Pillar {
+  pub center: [Dimension; 2],
+  pub radius: Dimension
+}
+
+impl Pillar {
+  pub fn position(&self) -> Position {
+    Position { x: self.center[0], y: self.center[1] }
+  }
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, PartialOrd, Deserialize, Serialize)]
@@ -29,7 +43,12 @@ pub struct Position {
   pub y: Dimension,
 }
 
-#[derive(Serialize, Deserialize)]
+pub const MAX_VOLUME: f64 = 10.0;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Solution {
-  pub placements: Vec<Position>
+  pub placements: Vec<Position>,
+  /// Per-musician volume in 0.0..=10.0, everyone plays at 1.0 when missing
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub volumes: Option<Vec<f64>>
 }
@@ -1,28 +1,64 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
 use std::rc::Rc;
-use indicatif::{ProgressBar, ProgressStyle};
 use mincost::{Particle, PsoConfig};
-use multimap::MultiMap;
 use rand::Rng;
-use crate::models::{Attendee, Instrument, Position, ProblemSpec};
-use parry2d::math::{Isometry, Point, Vector};
-use parry2d::shape::{Ball, Segment};
-use parry2d::bounding_volume::BoundingVolume;
-
+use rand::seq::SliceRandom;
+use crate::models::{Instrument, Position, ProblemSpec, Solution, MAX_VOLUME};
+use crate::{progress, scoring, validation};
+
+pub mod annealing;
+pub mod assignment;
+pub mod grid;
+pub mod perimeter;
 pub mod z3;
 
 
 #[derive(Clone, Copy, Hash, Ord, PartialOrd, Eq, PartialEq, Debug)]
 pub struct MusicianId(pub usize);
 
-#[derive(Clone, Copy, Hash, Ord, PartialOrd, Eq, PartialEq, Debug)]
-pub struct AttendeeId(usize);
 
 // Really 10 is supposed to be allowed, but I'm not sure if this works or not
 const ALLOWED_MUSICIAN_DISTANCE: f32 = 10.5;
 
-pub fn particle_swarm_optimizer(problem: &ProblemSpec) -> HashMap<MusicianId, Position> {
+// Perimeter seeds keep clear of ALLOWED_MUSICIAN_DISTANCE so the swarm's objective accepts them
+const SEED_SPACING: f32 = 11.0;
+
+// How far joint PSO particles start from the seed layout, per coordinate
+const JOINT_JITTER: f32 = 2.0;
+
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SwarmOptions {
+  /// Extra passes re-optimizing each musician with every other musician already on stage
+  pub sweeps: usize,
+  /// Finish with a PSO over all musicians at once, see [joint_optimizer]
+  pub joint: bool,
+  /// Start from a [perimeter::perimeter_optimizer] layout instead of placing musicians one at a time,
+  /// leaving sweeps to move them
+  pub perimeter_seed: bool,
+}
+
+/// Intermediate state of an optimizer run
+#[derive(Clone, Debug)]
+pub struct Snapshot {
+  /// Musicians placed so far
+  pub placements: HashMap<MusicianId, Position>,
+  /// Sum of each placed musician's objective when it was last placed, blocking only by musicians placed before it
+  pub score: f64,
+  /// Exact score of the finished layout, only set on the last snapshot
+  pub exact: Option<i64>,
+}
+
+pub fn particle_swarm_optimizer(problem: &ProblemSpec, options: SwarmOptions) -> HashMap<MusicianId, Position> {
+  particle_swarm_optimizer_with(problem, options, |_| {})
+}
+
+/// [particle_swarm_optimizer] reporting a [Snapshot] every time a musician is (re-)placed
+pub fn particle_swarm_optimizer_with(
+  problem: &ProblemSpec,
+  options: SwarmOptions,
+  mut on_progress: impl FnMut(Snapshot),
+) -> HashMap<MusicianId, Position> {
   let mus_inst: HashMap<MusicianId, Instrument> = problem.musicians.iter().copied().enumerate()
     .map(|(idx, inst)| (MusicianId(idx), inst))
     .collect();
@@ -35,18 +71,11 @@ pub fn particle_swarm_optimizer(problem: &ProblemSpec) -> HashMap<MusicianId, Po
   let x_end = problem.stage_bottom_left[0] + problem.stage_width - 10.0;
   let y_end = problem.stage_bottom_left[1] + problem.stage_height - 10.0;
 
-  let attendees: HashMap<usize, Attendee> = problem.attendees.iter().cloned().enumerate().collect();
-
+  let instrument_count = problem.attendees.first().map_or(0, |a| a.tastes.len());
 
-  let mut inst_scores: MultiMap<Instrument, (Position, f64)> = multimap::MultiMap::new();
+  let extensions = scoring::Extensions::for_problem(problem);
 
-  for (_id, attendee) in attendees {
-    for (inst, &score) in attendee.tastes.iter().enumerate() {
-      inst_scores.insert(Instrument(inst), (attendee.position, score));
-    }
-  }
-
-  let mut inst_score_functions: HashMap<Instrument, Box<dyn Fn(Position) -> f64>> = HashMap::new();
+  let mut inst_score_functions: HashMap<Instrument, Box<dyn Fn(Position) -> f64 + '_>> = HashMap::new();
   let musician_position_state_map = Rc::new(RefCell::new(mus_map));
 
   fn dist(p1: &Position, p2: &Position) -> f32 {
@@ -56,8 +85,9 @@ pub fn particle_swarm_optimizer(problem: &ProblemSpec) -> HashMap<MusicianId, Po
     f32::sqrt(del_x * del_x + del_y * del_y)
   }
 
-  for (inst, scores) in inst_scores {
+  for inst in (0..instrument_count).map(Instrument) {
     let m = Rc::clone(&musician_position_state_map);
+    let instruments = mus_inst.clone();
     inst_score_functions.insert(inst, Box::new(move |pos| {
       if !(x_start..=x_end).contains(&pos.x)
         || !(y_start..=y_end).contains(&pos.y) {
@@ -70,61 +100,31 @@ pub fn particle_swarm_optimizer(problem: &ProblemSpec) -> HashMap<MusicianId, Po
         }
       }
 
-      scores.iter().map(|(a_pos, taste)| {
-
-        // check if any musician other is in the way between pos and a_pos
-        let line_start = Point::new(a_pos.x, a_pos.y);
-        let line_end = Point::new(pos.x, pos.y);
-        let segment = Segment::new(line_start, line_end);
-        let segment_aabb = segment.aabb(&Isometry::translation(line_start.x, line_start.y));
-        let circle_radius = 5.0;
-        let circle_shape = Ball::new(circle_radius);
-
-        for other in m.borrow().values() {
-          if pos == *other {
-            continue
-          } else {
-            let circle_center = Point::new(other.x, other.y);
-            let circle_aabb = circle_shape.aabb(&Isometry::translation(circle_center.x, circle_center.y));
-
-            if segment_aabb.intersects(&circle_aabb) {
-              return 0.0
-            }
+      let others: Vec<Position> = m.borrow().values().copied().collect();
+      let total = -scoring::potential(problem, inst, pos, &others);
 
-            /*
-            let intersection = parry2d::query::intersection_test(
-              &Isometry::translation(line_start.x, line_start.y),
-              &segment,
-              &Isometry::translation(circle_center.x, circle_center.y),
-              &circle_shape);
-              */
-          }
-        }
-
-        let top = (*taste as f64) * 1_000_000.0f64;
-        let del_x = a_pos.x - pos.x;
-        let del_y = a_pos.y - pos.y;
-        let dist_sq = del_x * del_x + del_y * del_y;
+      if extensions.playing_together {
+        let closeness: f64 = 1.0 + m.borrow().iter()
+          .filter(|&(other, _)| instruments[other] == inst)
+          .map(|(_, other)| 1.0 / dist(&pos, other) as f64)
+          .sum::<f64>();
 
-        -(top / (dist_sq as f64))
-      }).sum()
+        total * closeness
+      } else {
+        total
+      }
     }));
   }
 
-  let pb = ProgressBar::new(problem.musicians.len() as u64 + 1);
-
-  let sty = ProgressStyle::with_template(
-    "{spinner:.green} [{elapsed_precise}] {wide_bar:.cyan/blue} {pos:>3}/{len} {msg}",
-  )
-    .unwrap()
-    .progress_chars("#>-");
-
-  pb.set_style(sty);
+  // group musicians by instrument so each layer of same-instrument players is placed together
+  let mut order: Vec<(MusicianId, Instrument)> = mus_inst.iter()
+    .map(|(&mus, &inst)| (mus, inst))
+    .collect();
+  order.sort_by_key(|&(mus, inst)| (inst, mus));
 
-  for (mus, inst) in mus_inst {
-    pb.set_message(format!("Optimizing {:?}", mus));
-    pb.inc(1);
+  let pb = progress::bar((order.len() * (options.sweeps + 1)) as u64 + 1);
 
+  let optimize_musician = |inst: Instrument| -> Position {
     let mut opt = mincost::PsOpt::init(
       PsoConfig {
         pop_size: 20,
@@ -142,20 +142,34 @@ pub fn particle_swarm_optimizer(problem: &ProblemSpec) -> HashMap<MusicianId, Po
       },
       || {
         let mut random = rand::thread_rng();
-        let x ;
-        let y ;
-
-        loop {
-          x = random.gen_range(x_start..=x_end);
-          y = random.gen_range(y_start..=y_end);
-
-          for other in musician_position_state_map.borrow().values() {
-            if dist(&Position{x,y}, other) <= ALLOWED_MUSICIAN_DISTANCE {
-              continue
+        let placed = musician_position_state_map.borrow();
+
+        // with playing together, seed half the swarm next to musicians already playing our instrument
+        let anchors: Vec<Position> = placed.iter()
+          .filter(|&(other, _)| extensions.playing_together && mus_inst[other] == inst)
+          .map(|(_, &pos)| pos)
+          .collect();
+
+        let mut x = random.gen_range(x_start..=x_end);
+        let mut y = random.gen_range(y_start..=y_end);
+
+        for _ in 0..100 {
+          match anchors.choose(&mut random) {
+            Some(anchor) if random.gen_bool(0.5) => {
+              let angle = random.gen_range(0.0..std::f32::consts::TAU);
+              let radius = random.gen_range(ALLOWED_MUSICIAN_DISTANCE..=3.0 * ALLOWED_MUSICIAN_DISTANCE);
+              x = (anchor.x + radius * angle.cos()).clamp(x_start, x_end);
+              y = (anchor.y + radius * angle.sin()).clamp(y_start, y_end);
+            }
+            _ => {
+              x = random.gen_range(x_start..=x_end);
+              y = random.gen_range(y_start..=y_end);
             }
           }
 
-          break;
+          if placed.values().all(|other| dist(&Position { x, y }, other) > ALLOWED_MUSICIAN_DISTANCE) {
+            break;
+          }
         }
 
         Particle {
@@ -168,13 +182,168 @@ pub fn particle_swarm_optimizer(problem: &ProblemSpec) -> HashMap<MusicianId, Po
 
     let best_position_for_musician = opt.optimize();
 
-    musician_position_state_map.borrow_mut().insert(mus, Position { x: best_position_for_musician[0], y: best_position_for_musician[1] });
+    Position { x: best_position_for_musician[0], y: best_position_for_musician[1] }
+  };
+
+  let mut objectives: HashMap<MusicianId, f64> = HashMap::new();
+
+  let mut report = |objectives: &HashMap<MusicianId, f64>| on_progress(Snapshot {
+    placements: musician_position_state_map.borrow().clone(),
+    score: -objectives.values().sum::<f64>(),
+    exact: None,
+  });
+
+  // a stage that fits everyone at the official spacing may still be too small for the seed's
+  let seed = if options.perimeter_seed {
+    perimeter::perimeter_layout(problem, SEED_SPACING)
+      .map_err(|err| eprintln!("Placing musicians one at a time, no perimeter seed: {:#}", err))
+      .ok()
+  } else {
+    None
+  };
+
+  if let Some(seed) = seed {
+    pb.set_message("Seeding from the perimeter");
+    musician_position_state_map.borrow_mut().extend(seed);
+
+    for &(mus, inst) in order.iter() {
+      pb.inc(1);
+
+      // scored like a sweep would, against everyone else on stage
+      let pos = musician_position_state_map.borrow_mut().remove(&mus).unwrap();
+      objectives.insert(mus, inst_score_functions[&inst](pos));
+      musician_position_state_map.borrow_mut().insert(mus, pos);
+    }
+
+    report(&objectives);
+  } else {
+    for &(mus, inst) in order.iter() {
+      pb.set_message(format!("Optimizing {:?}", mus));
+      pb.inc(1);
+
+      let best = optimize_musician(inst);
+      objectives.insert(mus, inst_score_functions[&inst](best));
+      musician_position_state_map.borrow_mut().insert(mus, best);
+      report(&objectives);
+    }
+  }
+
+  for sweep in 1..=options.sweeps {
+    for &(mus, inst) in order.iter() {
+      pb.set_message(format!("Sweep {} {:?}", sweep, mus));
+      pb.inc(1);
+
+      // take the musician off the stage so it's re-placed against everyone else, including later musicians
+      let current = musician_position_state_map.borrow_mut().remove(&mus).unwrap();
+      let func = inst_score_functions.get(&inst).unwrap();
+
+      let candidate = optimize_musician(inst);
+      let (best, objective) = [current, candidate].into_iter()
+        .map(|pos| (pos, func(pos)))
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .unwrap();
+
+      objectives.insert(mus, objective);
+      musician_position_state_map.borrow_mut().insert(mus, best);
+      report(&objectives);
+    }
   }
 
   pb.finish_with_message("Optimized");
 
-  let result = musician_position_state_map.borrow();
-  result.clone()
+  let result = musician_position_state_map.borrow().clone();
+
+  if options.joint {
+    joint_optimizer(problem, &result)
+  } else {
+    result
+  }
+}
+
+/// PSO over every musician's coordinates at once, each particle scored by the exact scorer as if
+/// [assign_volumes] had muted its musicians with negative contributions, like the result will be.
+/// Every evaluation is a full `scoring::score`, so this is best suited to refining smaller problems.
+pub fn joint_optimizer(problem: &ProblemSpec, seed: &HashMap<MusicianId, Position>) -> HashMap<MusicianId, Position> {
+  let seed: Vec<f32> = (0..problem.musicians.len())
+    .flat_map(|idx| {
+      let pos = seed[&MusicianId(idx)];
+      [pos.x, pos.y]
+    })
+    .collect();
+
+  let objective = |p: &[f32]| {
+    let solution = Solution {
+      placements: p.chunks(2).map(|c| Position { x: c[0], y: c[1] }).collect(),
+      volumes: None,
+    };
+
+    if !validation::violations(problem, &solution).is_empty() {
+      return f64::MAX
+    }
+
+    -(scoring::score(problem, &solution).musicians.iter().map(|&contribution| contribution.max(0)).sum::<i64>() as f64)
+  };
+
+  let pb = progress::spinner("Joint optimization");
+
+  // the first particle starts exactly on the seed so the swarm can never do worse than it
+  let seeded = Cell::new(false);
+
+  let mut opt = mincost::PsOpt::init(
+    PsoConfig {
+      pop_size: 10,
+      omega: 1.0,
+      phi_g: 0.1,
+      phi_p: 0.1,
+      learning_rate: 0.2,
+      iteration: 20,
+    },
+    |p| objective(p),
+    || {
+      let mut random = rand::thread_rng();
+
+      let position: Vec<f32> = if seeded.replace(true) {
+        seed.iter().map(|&c| c + random.gen_range(-JOINT_JITTER..=JOINT_JITTER)).collect()
+      } else {
+        seed.clone()
+      };
+
+      Particle {
+        velocity: vec![0f32; position.len()],
+        best_known_position: position.clone(),
+        position,
+      }
+    }
+  );
+
+  let best = opt.optimize();
+  let best = if objective(&best) <= objective(&seed) { best } else { seed };
+
+  pb.finish_with_message("Joint optimization done");
+
+  best.chunks(2).enumerate()
+    .map(|(idx, c)| (MusicianId(idx), Position { x: c[0], y: c[1] }))
+    .collect()
+}
+
+/// Orders placements by musician id into a `Solution` without volumes
+pub fn to_solution(placements: &HashMap<MusicianId, Position>) -> Solution {
+  Solution {
+    placements: (0..placements.len()).map(|idx| placements[&MusicianId(idx)]).collect(),
+    volumes: None,
+  }
 }
 
+/// Turns up every musician with a positive net contribution and mutes the rest
+pub fn assign_volumes(problem: &ProblemSpec, solution: &mut Solution) {
+  let unit_volume = Solution {
+    placements: solution.placements.clone(),
+    volumes: None,
+  };
+
+  let score = scoring::score(problem, &unit_volume);
 
+  solution.volumes = Some(score.musicians.iter()
+    .map(|&contribution| if contribution > 0 { MAX_VOLUME } else { 0.0 })
+    .collect());
+}
@@ -0,0 +1,362 @@
+use rand::Rng;
+use crate::models::{Attendee, Position, ProblemSpec, Solution};
+use crate::scoring::{self, Extensions, MUSICIAN_RADIUS};
+use crate::{progress, validation};
+
+// Largest distance a nudge moves a musician along each axis
+const NUDGE_DISTANCE: f32 = 20.0;
+
+const SWAP_PROBABILITY: f64 = 0.25;
+const TELEPORT_PROBABILITY: f64 = 0.15;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Schedule {
+  /// Temperature falls linearly from the start to the end temperature
+  Linear,
+  /// Temperature falls geometrically from the start to the end temperature
+  Exponential,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct AnnealOptions {
+  pub iterations: usize,
+  pub start_temperature: f64,
+  pub end_temperature: f64,
+  pub schedule: Schedule,
+}
+
+impl AnnealOptions {
+  fn temperature(&self, iteration: usize) -> f64 {
+    let progress = iteration as f64 / self.iterations.max(1) as f64;
+
+    match self.schedule {
+      Schedule::Linear =>
+        self.start_temperature + (self.end_temperature - self.start_temperature) * progress,
+      Schedule::Exponential =>
+        self.start_temperature * (self.end_temperature / self.start_temperature).powf(progress),
+    }
+  }
+}
+
+enum Move {
+  Relocate { musician: usize, from: Position },
+  Swap(usize, usize),
+}
+
+/// Incrementally maintained scoring state, so a move costs O(musicians * attendees)
+/// instead of a full rescore
+struct State<'a> {
+  problem: &'a ProblemSpec,
+  extensions: Extensions,
+  placements: Vec<Position>,
+  /// Number of musicians and pillars on the line between each musician and attendee
+  occluders: Vec<Vec<u32>>,
+  /// Impact of each musician summed over the attendees that can hear it
+  visible: Vec<f64>,
+  closeness: Vec<f64>,
+}
+
+impl<'a> State<'a> {
+  fn new(problem: &'a ProblemSpec, extensions: Extensions, placements: Vec<Position>) -> Self {
+    let musicians = placements.len();
+
+    let mut state = State {
+      problem,
+      extensions,
+      placements,
+      occluders: vec![Vec::new(); musicians],
+      visible: vec![0.0; musicians],
+      closeness: vec![1.0; musicians],
+    };
+
+    for musician in 0..musicians {
+      state.recount(musician);
+    }
+
+    let mut instruments = problem.musicians.clone();
+    instruments.sort();
+    instruments.dedup();
+
+    for inst in instruments {
+      state.update_closeness(inst.0);
+    }
+
+    state
+  }
+
+  fn impact(&self, musician: usize, attendee: &Attendee) -> f64 {
+    let inst = self.problem.musicians[musician];
+
+    scoring::impact(attendee.tastes[inst.0], attendee.position, self.placements[musician])
+  }
+
+  /// Objective being maximized: every musician with a positive contribution, as if volumes were tuned afterwards
+  fn objective(&self) -> f64 {
+    self.visible.iter().zip(self.closeness.iter())
+      .map(|(visible, closeness)| (visible * closeness).max(0.0))
+      .sum()
+  }
+
+  /// Recomputes every occluder on a musician's lines from scratch
+  fn recount(&mut self, musician: usize) {
+    let pos = self.placements[musician];
+    let mut visible = 0.0;
+
+    let row: Vec<u32> = self.problem.attendees.iter().map(|attendee| {
+      let musicians = self.placements.iter().enumerate()
+        .filter(|&(other, &other_pos)| other != musician
+          && scoring::segment_blocked(attendee.position, pos, other_pos, MUSICIAN_RADIUS))
+        .count();
+
+      let pillars = self.problem.pillars.iter()
+        .filter(|pillar| scoring::segment_blocked(attendee.position, pos, pillar.position(), pillar.radius as f64))
+        .count();
+
+      let count = (musicians + pillars) as u32;
+
+      if count == 0 {
+        visible += self.impact(musician, attendee);
+      }
+
+      count
+    }).collect();
+
+    self.occluders[musician] = row;
+    self.visible[musician] = visible;
+  }
+
+  fn visible_impact(&self, musician: usize) -> f64 {
+    self.problem.attendees.iter().zip(self.occluders[musician].iter())
+      .filter(|&(_, &count)| count == 0)
+      .map(|(attendee, _)| self.impact(musician, attendee))
+      .sum()
+  }
+
+  fn update_closeness(&mut self, inst: usize) {
+    if !self.extensions.playing_together {
+      return
+    }
+
+    for musician in 0..self.placements.len() {
+      if self.problem.musicians[musician].0 == inst {
+        self.closeness[musician] = scoring::closeness(self.problem, &self.placements, musician);
+      }
+    }
+  }
+
+  fn relocate(&mut self, musician: usize, to: Position) {
+    let problem = self.problem;
+    let from = self.placements[musician];
+
+    for other in 0..self.placements.len() {
+      if other == musician {
+        continue
+      }
+
+      let pos = self.placements[other];
+
+      for (a_idx, attendee) in problem.attendees.iter().enumerate() {
+        let was_blocking = scoring::segment_blocked(attendee.position, pos, from, MUSICIAN_RADIUS);
+        let is_blocking = scoring::segment_blocked(attendee.position, pos, to, MUSICIAN_RADIUS);
+
+        if was_blocking == is_blocking {
+          continue
+        }
+
+        let impact = self.impact(other, attendee);
+        let count = &mut self.occluders[other][a_idx];
+
+        if is_blocking {
+          if *count == 0 {
+            self.visible[other] -= impact;
+          }
+          *count += 1;
+        } else {
+          *count -= 1;
+          if *count == 0 {
+            self.visible[other] += impact;
+          }
+        }
+      }
+    }
+
+    self.placements[musician] = to;
+    self.recount(musician);
+    self.update_closeness(problem.musicians[musician].0);
+  }
+
+  /// Swapping keeps the set of occupied positions, so only the two swapped musicians need rescoring
+  fn swap(&mut self, first: usize, second: usize) {
+    self.placements.swap(first, second);
+    self.occluders.swap(first, second);
+
+    self.visible[first] = self.visible_impact(first);
+    self.visible[second] = self.visible_impact(second);
+
+    self.update_closeness(self.problem.musicians[first].0);
+    self.update_closeness(self.problem.musicians[second].0);
+  }
+
+  fn random_move(&mut self, random: &mut impl Rng) -> Option<Move> {
+    let musician = random.gen_range(0..self.placements.len());
+    let roll: f64 = random.gen();
+
+    if roll < SWAP_PROBABILITY {
+      let other = random.gen_range(0..self.placements.len());
+
+      if self.problem.musicians[musician] == self.problem.musicians[other] {
+        return None
+      }
+
+      self.swap(musician, other);
+      return Some(Move::Swap(musician, other))
+    }
+
+    let from = self.placements[musician];
+
+    let to = if roll < SWAP_PROBABILITY + TELEPORT_PROBABILITY {
+      let (bottom_left, top_right) = validation::stage_bounds(self.problem);
+
+      Position {
+        x: random.gen_range(bottom_left.x..=top_right.x),
+        y: random.gen_range(bottom_left.y..=top_right.y),
+      }
+    } else {
+      Position {
+        x: from.x + random.gen_range(-NUDGE_DISTANCE..=NUDGE_DISTANCE),
+        y: from.y + random.gen_range(-NUDGE_DISTANCE..=NUDGE_DISTANCE),
+      }
+    };
+
+    if !validation::fits(self.problem, &self.placements, musician, to) {
+      return None
+    }
+
+    self.relocate(musician, to);
+    Some(Move::Relocate { musician, from })
+  }
+
+  fn undo(&mut self, applied: Move) {
+    match applied {
+      Move::Relocate { musician, from } => self.relocate(musician, from),
+      Move::Swap(first, second) => self.swap(first, second),
+    }
+  }
+}
+
+/// Improves a valid starting solution with simulated annealing over nudge, swap and teleport moves.
+/// The result has its volumes assigned with [super::assign_volumes].
+pub fn anneal(problem: &ProblemSpec, start: &Solution, options: AnnealOptions) -> Solution {
+  let mut state = State::new(problem, Extensions::for_problem(problem), start.placements.clone());
+  let mut random = rand::thread_rng();
+
+  let mut current = state.objective();
+  let mut best = current;
+  let mut best_placements = state.placements.clone();
+
+  let pb = progress::bar(options.iterations as u64);
+
+  for iteration in 0..options.iterations {
+    if iteration % 100 == 0 {
+      pb.set_position(iteration as u64);
+      pb.set_message(format!("best {:.0}", best));
+    }
+
+    if state.placements.is_empty() {
+      break
+    }
+
+    let Some(applied) = state.random_move(&mut random) else {
+      continue
+    };
+
+    let candidate = state.objective();
+    let delta = candidate - current;
+    let temperature = options.temperature(iteration);
+
+    if delta >= 0.0 || random.gen::<f64>() < (delta / temperature).exp() {
+      current = candidate;
+
+      if current > best {
+        best = current;
+        best_placements = state.placements.clone();
+      }
+    } else {
+      state.undo(applied);
+    }
+  }
+
+  pb.finish_with_message(format!("best {:.0}", best));
+
+  let mut solution = Solution {
+    placements: best_placements,
+    volumes: None,
+  };
+
+  super::assign_volumes(problem, &mut solution);
+  solution
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const PROBLEM_JSON: &str = include_str!("../../problems/problem-42.json");
+
+  fn problem() -> ProblemSpec {
+    serde_json::from_str(PROBLEM_JSON).unwrap()
+  }
+
+  fn start(problem: &ProblemSpec) -> Solution {
+    let (bottom_left, _) = validation::stage_bounds(problem);
+
+    Solution {
+      placements: (0..problem.musicians.len())
+        .map(|idx| Position { x: bottom_left.x + 20.0 * idx as f32, y: bottom_left.y })
+        .collect(),
+      volumes: None,
+    }
+  }
+
+  #[test]
+  fn incremental_state_matches_recount() {
+    let problem = problem();
+    let mut state = State::new(&problem, Extensions { playing_together: true }, start(&problem).placements);
+    let mut random = rand::thread_rng();
+
+    for _ in 0..200 {
+      if let Some(applied) = state.random_move(&mut random) {
+        if random.gen_bool(0.5) {
+          state.undo(applied);
+        }
+      }
+    }
+
+    let fresh = State::new(&problem, state.extensions, state.placements.clone());
+
+    assert_eq!(state.occluders, fresh.occluders);
+    assert_eq!(state.visible, fresh.visible);
+    assert_eq!(state.closeness, fresh.closeness);
+  }
+
+  #[test]
+  fn anneal_keeps_solution_valid() {
+    let problem = problem();
+    let start = start(&problem);
+
+    let solution = anneal(&problem, &start, AnnealOptions {
+      iterations: 500,
+      start_temperature: 1000.0,
+      end_temperature: 1.0,
+      schedule: Schedule::Exponential,
+    });
+
+    assert!(validation::validate(&problem, &solution).is_ok());
+
+    // the result carries tuned volumes, so the start gets them too
+    let mut tuned = start.clone();
+    crate::optimizer::assign_volumes(&problem, &mut tuned);
+
+    assert!(scoring::score(&problem, &solution).total >= scoring::score(&problem, &tuned).total);
+  }
+}
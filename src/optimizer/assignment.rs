@@ -0,0 +1,139 @@
+use std::collections::{BTreeSet, HashMap};
+use pathfinding::prelude::{kuhn_munkres, Matrix};
+use crate::models::{Position, ProblemSpec, Solution};
+use crate::scoring::{self, MUSICIAN_RADIUS};
+use crate::progress;
+use crate::validation::{self, MUSICIAN_SPACING};
+use super::MusicianId;
+
+/// Every legal stage position on a square grid `MUSICIAN_SPACING` apart
+pub fn grid_slots(problem: &ProblemSpec) -> Vec<Position> {
+  let (bottom_left, top_right) = validation::stage_bounds(problem);
+  let spacing = MUSICIAN_SPACING as f32;
+
+  let columns = ((top_right.x - bottom_left.x) / spacing).floor() as usize + 1;
+  let rows = ((top_right.y - bottom_left.y) / spacing).floor() as usize + 1;
+
+  (0..rows)
+    .flat_map(|row| (0..columns).map(move |column| Position {
+      x: bottom_left.x + spacing * column as f32,
+      y: bottom_left.y + spacing * row as f32,
+    }))
+    .collect()
+}
+
+/// Score of every instrument at every slot, indexed `[instrument][slot]`.
+/// Pillars always block, other musicians only block when `placements` is given.
+pub(super) fn slot_scores(problem: &ProblemSpec, slots: &[Position], placements: Option<&[Position]>) -> Vec<Vec<i64>> {
+  let instruments = problem.attendees.first().map_or(0, |a| a.tastes.len());
+  let mut scores = vec![vec![0i64; slots.len()]; instruments];
+
+  for (s_idx, &slot) in slots.iter().enumerate() {
+    for attendee in problem.attendees.iter() {
+      let pillar_blocked = problem.pillars.iter()
+        .any(|pillar| scoring::segment_blocked(attendee.position, slot, pillar.position(), pillar.radius as f64));
+
+      let musician_blocked = placements.is_some_and(|placements| placements.iter()
+        .any(|&other| other != slot && scoring::segment_blocked(attendee.position, slot, other, MUSICIAN_RADIUS)));
+
+      if pillar_blocked || musician_blocked {
+        continue
+      }
+
+      for (inst, &taste) in attendee.tastes.iter().enumerate() {
+        scores[inst][s_idx] += scoring::impact(taste, attendee.position, slot) as i64;
+      }
+    }
+  }
+
+  scores
+}
+
+/// Assigns musicians to slots maximizing the total score with the Hungarian algorithm.
+/// Negative scores count as zero since those musicians get muted by [super::assign_volumes].
+pub(super) fn assign(problem: &ProblemSpec, slots: &[Position], scores: &[Vec<i64>]) -> Vec<Position> {
+  let musicians = problem.musicians.len();
+
+  // any slot outside an instrument's top `musicians` can always be traded for an unused one from that list,
+  // so only the union of those lists is needed as columns
+  let mut candidates: BTreeSet<usize> = BTreeSet::new();
+
+  for inst_scores in scores.iter() {
+    let mut ranked: Vec<usize> = (0..slots.len()).collect();
+    ranked.sort_by_key(|&s_idx| std::cmp::Reverse(inst_scores[s_idx]));
+    candidates.extend(ranked.into_iter().take(musicians));
+  }
+
+  let candidates: Vec<usize> = candidates.into_iter().collect();
+
+  let weights: Vec<i64> = problem.musicians.iter()
+    .flat_map(|inst| candidates.iter().map(move |&s_idx| scores[inst.0][s_idx].max(0)))
+    .collect();
+
+  let weights = Matrix::from_vec(musicians, candidates.len(), weights).unwrap();
+  let (_total, columns) = kuhn_munkres(&weights);
+
+  columns.into_iter().map(|column| slots[candidates[column]]).collect()
+}
+
+/// Deterministic baseline: score every (instrument, grid slot) pair ignoring other musicians and solve the
+/// assignment problem. Each blocking round rescores the slots with the previous layout's musicians as
+/// occluders and re-solves, keeping whichever layout has the best exact score.
+pub fn assignment_optimizer(problem: &ProblemSpec, blocking_rounds: usize) -> Result<HashMap<MusicianId, Position>, anyhow::Error> {
+  let slots = grid_slots(problem);
+
+  if slots.len() < problem.musicians.len() {
+    anyhow::bail!("stage only fits {} of {} musicians", slots.len(), problem.musicians.len());
+  }
+
+  let pb = progress::bar(blocking_rounds as u64 + 1);
+  pb.set_message(format!("Scoring {} slots", slots.len()));
+
+  let mut placements = assign(problem, &slots, &slot_scores(problem, &slots, None));
+  let mut best = (tuned_score(problem, &placements), placements.clone());
+
+  for round in 1..=blocking_rounds {
+    pb.inc(1);
+    pb.set_message(format!("Blocking round {}", round));
+
+    placements = assign(problem, &slots, &slot_scores(problem, &slots, Some(&placements)));
+
+    let score = tuned_score(problem, &placements);
+
+    if score > best.0 {
+      best = (score, placements.clone());
+    }
+  }
+
+  pb.finish_with_message(format!("Assigned, score {}", best.0));
+
+  Ok(best.1.into_iter().enumerate()
+    .map(|(idx, pos)| (MusicianId(idx), pos))
+    .collect())
+}
+
+fn tuned_score(problem: &ProblemSpec, placements: &[Position]) -> i64 {
+  let mut solution = Solution {
+    placements: placements.to_vec(),
+    volumes: None,
+  };
+
+  super::assign_volumes(problem, &mut solution);
+  scoring::score(problem, &solution).total
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::optimizer::to_solution;
+  use super::*;
+
+  const PROBLEM_JSON: &str = include_str!("../../problems/problem-42.json");
+
+  #[test]
+  fn assigns_valid_layout() {
+    let problem: ProblemSpec = serde_json::from_str(PROBLEM_JSON).unwrap();
+    let solution = to_solution(&assignment_optimizer(&problem, 1).unwrap());
+
+    assert!(validation::validate(&problem, &solution).is_ok());
+  }
+}
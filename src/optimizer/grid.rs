@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+use crate::models::{Position, ProblemSpec};
+use crate::progress;
+use crate::validation::{self, MUSICIAN_SPACING};
+use super::assignment::{self, slot_scores};
+use super::MusicianId;
+
+/// Keeps hex rows from ending up a rounding error closer than `MUSICIAN_SPACING`
+const HEX_SLACK: f32 = 1e-3;
+
+/// How stage slots are laid out
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Packing {
+  /// Rows and columns `MUSICIAN_SPACING` apart
+  Square,
+  /// Rows offset by half a spacing, fitting about 15% more musicians
+  Hex,
+  /// Rings along the stage edges, outermost first
+  Perimeter,
+}
+
+/// Every legal stage position of a packing. Perimeter slots are ordered outermost ring first.
+pub fn slots(problem: &ProblemSpec, packing: Packing) -> Vec<Position> {
+  match packing {
+    Packing::Square => assignment::grid_slots(problem),
+    Packing::Hex => hex_slots(problem),
+    Packing::Perimeter => perimeter_slots(problem),
+  }
+}
+
+fn hex_slots(problem: &ProblemSpec) -> Vec<Position> {
+  let (bottom_left, top_right) = validation::stage_bounds(problem);
+  let spacing = MUSICIAN_SPACING as f32;
+  let pitch = (MUSICIAN_SPACING * 3f64.sqrt() / 2.0) as f32 + HEX_SLACK;
+
+  let mut slots = Vec::new();
+
+  for row in 0.. {
+    let y = bottom_left.y + pitch * row as f32;
+
+    if y > top_right.y {
+      break
+    }
+
+    let offset = if row % 2 == 1 { spacing / 2.0 } else { 0.0 };
+
+    for column in 0.. {
+      let x = bottom_left.x + offset + spacing * column as f32;
+
+      if x > top_right.x {
+        break
+      }
+
+      slots.push(Position { x, y });
+    }
+  }
+
+  slots
+}
+
+/// `from`, then every `spacing` up to `to`, which is always included as long as it's a full spacing away
+fn steps(from: f32, to: f32, spacing: f32) -> Vec<f32> {
+  let mut steps: Vec<f32> = (0..)
+    .map(|step| from + spacing * step as f32)
+    .take_while(|&value| value <= to - spacing)
+    .collect();
+
+  match steps.last() {
+    None => steps.push(from),
+    Some(&last) if to - last >= spacing => steps.push(to),
+    Some(_) => {}
+  }
+
+  steps
+}
+
+fn perimeter_slots(problem: &ProblemSpec) -> Vec<Position> {
+  rings(problem, MUSICIAN_SPACING as f32).into_iter().flatten().collect()
+}
+
+/// Slots `spacing` apart along the stage edges, then along rings a spacing further inside each time
+pub(super) fn rings(problem: &ProblemSpec, spacing: f32) -> Vec<Vec<Position>> {
+  let (mut bottom_left, mut top_right) = validation::stage_bounds(problem);
+
+  let mut rings = Vec::new();
+
+  // every ring stays a full spacing inside the previous one
+  while bottom_left.x <= top_right.x && bottom_left.y <= top_right.y {
+    let mut slots = Vec::new();
+    let xs = steps(bottom_left.x, top_right.x, spacing);
+    let ys = steps(bottom_left.y, top_right.y, spacing);
+
+    let (first_x, last_x) = (xs[0], xs[xs.len() - 1]);
+    let (first_y, last_y) = (ys[0], ys[ys.len() - 1]);
+
+    slots.extend(xs.iter().map(|&x| Position { x, y: first_y }));
+
+    if ys.len() > 1 {
+      slots.extend(xs.iter().map(|&x| Position { x, y: last_y }));
+    }
+
+    for &y in ys.iter().skip(1).take(ys.len().saturating_sub(2)) {
+      slots.push(Position { x: first_x, y });
+
+      if xs.len() > 1 {
+        slots.push(Position { x: last_x, y });
+      }
+    }
+
+    rings.push(slots);
+
+    bottom_left = Position { x: bottom_left.x + spacing, y: bottom_left.y + spacing };
+    top_right = Position { x: top_right.x - spacing, y: top_right.y - spacing };
+  }
+
+  rings
+}
+
+/// Musicians in the first slots of the packing in order, without looking at attendees.
+/// `None` when the stage can't fit every musician.
+pub fn layout(problem: &ProblemSpec, packing: Packing) -> Option<Vec<Position>> {
+  let slots = slots(problem, packing);
+
+  (slots.len() >= problem.musicians.len()).then(|| slots[..problem.musicians.len()].to_vec())
+}
+
+/// Like [assignment::assignment_optimizer] without blocking rounds, but on the slots of any packing
+pub fn grid_optimizer(problem: &ProblemSpec, packing: Packing) -> Result<HashMap<MusicianId, Position>, anyhow::Error> {
+  let slots = slots(problem, packing);
+
+  if slots.len() < problem.musicians.len() {
+    anyhow::bail!("{:?} packing only fits {} of {} musicians", packing, slots.len(), problem.musicians.len());
+  }
+
+  let pb = progress::bar(1);
+  pb.set_message(format!("Scoring {} {:?} slots", slots.len(), packing));
+
+  let placements = assignment::assign(problem, &slots, &slot_scores(problem, &slots, None));
+
+  pb.finish_with_message("Assigned");
+
+  Ok(placements.into_iter().enumerate()
+    .map(|(idx, pos)| (MusicianId(idx), pos))
+    .collect())
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::models::Solution;
+  use crate::optimizer::to_solution;
+  use crate::scoring;
+  use super::*;
+
+  const PROBLEM_JSON: &str = include_str!("../../problems/problem-42.json");
+
+  #[test]
+  fn slots_keep_their_distance() {
+    let problem: ProblemSpec = serde_json::from_str(PROBLEM_JSON).unwrap();
+
+    for packing in [Packing::Square, Packing::Hex, Packing::Perimeter] {
+      let slots = slots(&problem, packing);
+
+      assert!(slots.iter().all(|&slot| validation::on_stage(&problem, slot)), "{:?}", packing);
+
+      for (idx, &slot) in slots.iter().enumerate() {
+        assert!(slots[idx + 1..].iter().all(|&other| scoring::dist_squared(slot, other) >= MUSICIAN_SPACING * MUSICIAN_SPACING),
+          "{:?} slot {} is too close to another", packing, idx);
+      }
+    }
+
+    assert!(slots(&problem, Packing::Hex).len() > slots(&problem, Packing::Square).len());
+  }
+
+  #[test]
+  fn perimeter_starts_on_the_stage_edge() {
+    let problem: ProblemSpec = serde_json::from_str(PROBLEM_JSON).unwrap();
+    let (bottom_left, top_right) = validation::stage_bounds(&problem);
+    let slots = slots(&problem, Packing::Perimeter);
+
+    let on_edge = |pos: &Position| pos.x == bottom_left.x || pos.x == top_right.x
+      || pos.y == bottom_left.y || pos.y == top_right.y;
+
+    let outer = slots.iter().take_while(|pos| on_edge(pos)).count();
+
+    assert!(outer > 0);
+    assert!(slots[outer..].iter().all(|pos| !on_edge(pos)));
+  }
+
+  #[test]
+  fn layouts_are_valid() {
+    let problem: ProblemSpec = serde_json::from_str(PROBLEM_JSON).unwrap();
+
+    for packing in [Packing::Square, Packing::Hex, Packing::Perimeter] {
+      let placements = layout(&problem, packing).unwrap();
+      assert!(validation::validate(&problem, &Solution { placements, volumes: None }).is_ok(), "{:?}", packing);
+
+      let solution = to_solution(&grid_optimizer(&problem, packing).unwrap());
+      assert!(validation::validate(&problem, &solution).is_ok(), "{:?}", packing);
+    }
+  }
+
+  #[test]
+  fn rejects_crowded_stages() {
+    let mut problem: ProblemSpec = serde_json::from_str(PROBLEM_JSON).unwrap();
+    problem.stage_width = 20.0;
+    problem.stage_height = 20.0;
+
+    assert!(layout(&problem, Packing::Square).is_none());
+    assert!(grid_optimizer(&problem, Packing::Square).is_err());
+  }
+}
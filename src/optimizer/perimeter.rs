@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+use crate::models::{Position, ProblemSpec};
+use crate::{progress, scoring};
+use crate::validation::{self, MUSICIAN_SPACING};
+use super::assignment::{self, slot_scores};
+use super::{grid, MusicianId};
+
+/// What a slot off the edge is worth to any instrument, just above the zero unwanted musicians score on the edge
+const FILLER_SCORE: i64 = 1;
+
+/// Σ 1/d² over the attendees on the outer side of the stage edges `slot` lies on.
+/// Musicians there reach those attendees without anyone on stage in between.
+fn facing(problem: &ProblemSpec, slot: Position) -> f64 {
+  let (bottom_left, top_right) = validation::stage_bounds(problem);
+
+  problem.attendees.iter()
+    .filter(|attendee| {
+      let pos = attendee.position;
+
+      (slot.x == bottom_left.x && pos.x < slot.x) || (slot.x == top_right.x && pos.x > slot.x)
+        || (slot.y == bottom_left.y && pos.y < slot.y) || (slot.y == top_right.y && pos.y > slot.y)
+    })
+    .map(|attendee| 1.0 / scoring::dist_squared(attendee.position, slot))
+    .sum()
+}
+
+/// The `count` slots facing the most audience, best first
+fn best_facing(problem: &ProblemSpec, slots: Vec<Position>, count: usize) -> Vec<Position> {
+  let mut ranked: Vec<(Position, f64)> = slots.into_iter().map(|slot| (slot, facing(problem, slot))).collect();
+  ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+  ranked.into_iter().take(count).map(|(slot, _)| slot).collect()
+}
+
+/// Puts musicians on the stage edge slots facing the most audience, with the instruments that score best
+/// there. Everyone else, including musicians nobody on that side wants to hear, fills the next rings inside.
+pub fn perimeter_optimizer(problem: &ProblemSpec) -> Result<HashMap<MusicianId, Position>, anyhow::Error> {
+  perimeter_layout(problem, MUSICIAN_SPACING as f32)
+}
+
+/// [perimeter_optimizer] with slots `spacing` apart
+pub(super) fn perimeter_layout(problem: &ProblemSpec, spacing: f32) -> Result<HashMap<MusicianId, Position>, anyhow::Error> {
+  let musicians = problem.musicians.len();
+  let mut rings = grid::rings(problem, spacing).into_iter();
+
+  let edge = rings.next().unwrap_or_default();
+  let fillers: Vec<Position> = rings.flatten().take(musicians).collect();
+
+  if edge.len() + fillers.len() < musicians {
+    anyhow::bail!("stage only fits {} of {} musicians {} apart", edge.len() + fillers.len(), musicians, spacing);
+  }
+
+  let pb = progress::bar(1);
+  pb.set_message(format!("Scoring {} edge slots", edge.len()));
+
+  // edge slots past the best `musicians` would stay empty anyway
+  let edge = best_facing(problem, edge, musicians);
+
+  // fillers are worth next to nothing, so musicians only end up there when no edge slot suits them.
+  // assign counts negative scores as zero, which a filler then beats.
+  let mut scores = slot_scores(problem, &edge, None);
+  for inst_scores in scores.iter_mut() {
+    inst_scores.resize(edge.len() + fillers.len(), FILLER_SCORE);
+  }
+
+  let slots: Vec<Position> = edge.into_iter().chain(fillers).collect();
+  let placements = assignment::assign(problem, &slots, &scores);
+
+  pb.finish_with_message("Assigned");
+
+  Ok(placements.into_iter().enumerate()
+    .map(|(idx, pos)| (MusicianId(idx), pos))
+    .collect())
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::models::{Attendee, Instrument};
+  use crate::optimizer::{particle_swarm_optimizer_with, to_solution, SwarmOptions, SEED_SPACING};
+  use super::*;
+
+  const PROBLEM_JSON: &str = include_str!("../../problems/problem-42.json");
+
+  /// Everyone sits left of the stage, loving instrument 0 and hating instrument 1
+  fn audience_on_the_left() -> ProblemSpec {
+    ProblemSpec {
+      room_height: 200.0,
+      room_width: 200.0,
+      stage_height: 50.0,
+      stage_width: 50.0,
+      stage_bottom_left: [100.0, 100.0],
+      musicians: vec![Instrument(0), Instrument(1)],
+      attendees: [110.0, 125.0, 140.0].into_iter()
+        .map(|y| Attendee { position: Position { x: 50.0, y }, tastes: vec![1000.0, -1000.0] })
+        .collect(),
+      pillars: vec![],
+    }
+  }
+
+  #[test]
+  fn plays_towards_the_audience() {
+    let problem = audience_on_the_left();
+    let (bottom_left, _) = validation::stage_bounds(&problem);
+    let ring = grid::rings(&problem, MUSICIAN_SPACING as f32).swap_remove(0);
+
+    let ranked = best_facing(&problem, ring.clone(), ring.len());
+    let facing_left = ring.iter().filter(|slot| slot.x == bottom_left.x).count();
+
+    assert!(ranked[..facing_left].iter().all(|slot| slot.x == bottom_left.x), "{:?}", ranked);
+    assert!(ranked.windows(2).all(|pair| facing(&problem, pair[0]) >= facing(&problem, pair[1])));
+
+    let placements = perimeter_optimizer(&problem).unwrap();
+
+    assert_eq!(placements[&MusicianId(0)].x, bottom_left.x);
+    assert!(!ring.contains(&placements[&MusicianId(1)]), "{:?}", placements[&MusicianId(1)]);
+  }
+
+  #[test]
+  fn seeds_the_swarm() {
+    let problem = audience_on_the_left();
+    let options = SwarmOptions { perimeter_seed: true, ..Default::default() };
+
+    let mut snapshots = Vec::new();
+    let placements = particle_swarm_optimizer_with(&problem, options, |snapshot| snapshots.push(snapshot));
+
+    // without sweeps the seed is reported once and kept as is
+    let seed = perimeter_layout(&problem, SEED_SPACING).unwrap();
+
+    assert_eq!(snapshots.len(), 1);
+    assert_eq!(snapshots[0].placements, seed);
+    assert!(snapshots[0].score > 0.0);
+    assert_eq!(placements, seed);
+  }
+
+  #[test]
+  fn places_musicians_on_the_edge() {
+    let problem: ProblemSpec = serde_json::from_str(PROBLEM_JSON).unwrap();
+    let placements = perimeter_optimizer(&problem).unwrap();
+    let solution = to_solution(&placements);
+
+    assert!(validation::validate(&problem, &solution).is_ok());
+
+    // only the edge slots facing the most audience are used, and only by musicians scoring above a filler there
+    let ring = grid::rings(&problem, MUSICIAN_SPACING as f32).swap_remove(0);
+    let edge = best_facing(&problem, ring, problem.musicians.len());
+    let scores = slot_scores(&problem, &edge, None);
+    let slot_score = |musician: usize, pos: &Position| edge.iter().position(|slot| slot == pos)
+      .map(|s_idx| scores[problem.musicians[musician].0][s_idx]);
+
+    for (musician, pos) in solution.placements.iter().enumerate() {
+      match slot_score(musician, pos) {
+        Some(score) => assert!(score >= FILLER_SCORE, "musician {} scores {} at {:?}", musician, score, pos),
+        // moving onto an empty edge slot must not beat the filler
+        None => for (s_idx, slot) in edge.iter().enumerate() {
+          if !solution.placements.contains(slot) {
+            assert!(scores[problem.musicians[musician].0][s_idx] <= FILLER_SCORE, "musician {} left off {:?}", musician, slot);
+          }
+        },
+      }
+    }
+  }
+}
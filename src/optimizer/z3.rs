@@ -1,116 +1,156 @@
 use std::collections::HashMap;
-use ::z3::Symbol;
-use z3::ast::Float;
-use crate::models::{Attendee, Instrument, Position, ProblemSpec};
-use super::{AttendeeId, MusicianId};
+use z3::ast::{Bool, Int};
+use crate::models::{Position, ProblemSpec};
+use crate::validation::{self, MUSICIAN_SPACING};
+use super::MusicianId;
+
+#[derive(Copy, Clone, Debug)]
+pub struct Z3Options {
+  /// Give up and return the best model found so far after this many milliseconds
+  pub timeout_ms: Option<u64>,
+  /// Number of bins along each stage axis used to approximate Σ taste/d²
+  pub bins: usize,
+}
 
-pub fn optimize(problem: ProblemSpec) -> HashMap<MusicianId, Position> {
+/// A rectangle of the stage whose score is approximated by the score at its center
+struct Bin {
+  x_range: (i64, i64),
+  y_range: (i64, i64),
+  center: Position,
+}
 
-  let musicians: HashMap<MusicianId, Instrument> = problem.musicians.iter()
-    .copied()
-    .enumerate()
-    .map(|(idx, inst)| (MusicianId(idx), inst))
-    .collect();
+fn axis_ranges(start: i64, end: i64, bins: usize) -> Vec<(i64, i64)> {
+  let extent = end - start + 1;
+  let size = (extent / bins.max(1) as i64).max(MUSICIAN_SPACING as i64);
+
+  (start..=end).step_by(size as usize)
+    .map(|lo| (lo, (lo + size - 1).min(end)))
+    .collect()
+}
 
-  let attendees: HashMap<AttendeeId, Attendee> = problem.attendees.clone().into_iter()
-    .enumerate()
-    .map(|(idx, a)| (AttendeeId(idx), a))
+/// Places musicians on integer coordinates inside the stage margin, at least 10 apart on one axis
+/// (which implies the euclidean spacing), maximizing a piecewise-constant approximation of Σ taste/d²
+/// where each musician scores the value at the center of the bin it lands in.
+pub fn optimize(problem: &ProblemSpec, options: Z3Options) -> Option<HashMap<MusicianId, Position>> {
+  let (bottom_left, top_right) = validation::stage_bounds(problem);
+
+  let x_start = bottom_left.x.ceil() as i64;
+  let y_start = bottom_left.y.ceil() as i64;
+  let x_end = top_right.x.floor() as i64;
+  let y_end = top_right.y.floor() as i64;
+
+  let bins: Vec<Bin> = axis_ranges(y_start, y_end, options.bins).into_iter()
+    .flat_map(|y_range| axis_ranges(x_start, x_end, options.bins).into_iter()
+      .map(move |x_range| Bin {
+        x_range,
+        y_range,
+        center: Position {
+          x: (x_range.0 + x_range.1) as f32 / 2.0,
+          y: (y_range.0 + y_range.1) as f32 / 2.0,
+        },
+      }))
     .collect();
 
-  let config = z3::Config::new();
-  let ctx = z3::Context::new(&config);
-  let optimize = z3::Optimize::new(&ctx);
+  let centers: Vec<Position> = bins.iter().map(|bin| bin.center).collect();
+  let bin_scores = super::assignment::slot_scores(problem, &centers, None);
 
-  type Score<'a> = z3::ast::Int<'a>;
+  let mut config = z3::Config::new();
 
-  #[derive(PartialEq, Eq, Clone, Debug)]
-  struct MusicianSymbols<'a> {
-    x_var: Score<'a>,
-    y_var: Score<'a>
+  if let Some(timeout_ms) = options.timeout_ms {
+    config.set_timeout_msec(timeout_ms);
   }
 
-  let musician_symbol_map: HashMap<MusicianId, MusicianSymbols> = musicians.keys()
-    .copied()
-    .map(|m| {
-      let x_symbol = Symbol::String(format!("M{}-X", m.0));
-      let y_symbol = Symbol::String(format!("M{}-Y", m.0));
-      let x_var = Score::new_const(&ctx, x_symbol);
-      let y_var = Score::new_const(&ctx, y_symbol);
-      (m, MusicianSymbols {
-        x_var,
-        y_var
-      })
-    }).collect();
+  let ctx = z3::Context::new(&config);
+  let optimize = z3::Optimize::new(&ctx);
 
-  let attendee_tastes: HashMap<AttendeeId, HashMap<MusicianId, f64>> =  attendees.iter().map(|(a_id, a)| {
-    (*a_id, musicians.iter().map(|(m, inst)| {
-      (*m, 1_000_000f64 * a.tastes[inst.0])
-    }).collect())
-  }).collect();
+  struct MusicianSymbols<'a> {
+    x_var: Int<'a>,
+    y_var: Int<'a>,
+  }
 
-  let scores: Vec<Float<'_>> = attendees.iter().flat_map(|(attendees_id, a)| {
-    let x = a.position.x as i64;
-    let y = a.position.y as i64;
-    let attendees_id = *attendees_id;
+  let musician_symbols: Vec<MusicianSymbols> = (0..problem.musicians.len())
+    .map(|m| MusicianSymbols {
+      x_var: Int::new_const(&ctx, format!("M{}-X", m)),
+      y_var: Int::new_const(&ctx, format!("M{}-Y", m)),
+    })
+    .collect();
 
-    musician_symbol_map.iter()
-      .map(move |(musician_id, symbols)| {
-        (attendees_id, x, y, *musician_id, symbols.clone())
-      })
-  }).map(|(attendees_id, x, y, musician_id, symbols)| {
-    let x: i64 = x;
-    let y: i64 = y;
-    let x_dist = symbols.x_var.clone() - x;
-    let y_dist = symbols.y_var.clone() - y;
+  let int = |value: i64| Int::from_i64(&ctx, value);
 
-    let _dist_squared = x_dist.clone() * x_dist + y_dist.clone() * y_dist;
-    let calculated_score = attendee_tastes
-      .get(&attendees_id).unwrap()
-      .get(&musician_id).copied().unwrap();
+  for syms in musician_symbols.iter() {
+    optimize.assert(&syms.x_var.ge(&int(x_start)));
+    optimize.assert(&syms.y_var.ge(&int(y_start)));
+    optimize.assert(&syms.x_var.le(&int(x_end)));
+    optimize.assert(&syms.y_var.le(&int(y_end)));
+  }
 
-    let score = Float::from_f64(&ctx, calculated_score);
+  let spacing = int(MUSICIAN_SPACING as i64);
 
-    //let adjusted = score/dist_squared;
+  for (idx, first) in musician_symbols.iter().enumerate() {
+    for second in musician_symbols.iter().skip(idx + 1) {
+      let apart = [
+        (&first.x_var - &second.x_var).ge(&spacing),
+        (&second.x_var - &first.x_var).ge(&spacing),
+        (&first.y_var - &second.y_var).ge(&spacing),
+        (&second.y_var - &first.y_var).ge(&spacing),
+      ];
 
-   score
-  }).collect();
+      optimize.assert(&Bool::or(&ctx, &apart.iter().collect::<Vec<_>>()));
+    }
+  }
 
-  let x_start = (problem.stage_bottom_left[0] as i64) + 10;
-  let y_start = (problem.stage_bottom_left[1] as i64) + 10;
+  let zero = int(0);
+  let mut terms: Vec<Int> = vec![zero.clone()];
 
-  let x_end = x_start - 20 + (problem.room_width as i64);
-  let y_end = y_start - 20 + (problem.stage_height as i64);
+  for (m, (syms, inst)) in musician_symbols.iter().zip(problem.musicians.iter()).enumerate() {
+    for (b, bin) in bins.iter().enumerate() {
+      let in_bin = Bool::new_const(&ctx, format!("M{}-B{}", m, b));
 
-  let x_start = z3::ast::Int::from_i64(&ctx, x_start);
-  let y_start = z3::ast::Int::from_i64(&ctx, y_start);
-  let x_end = z3::ast::Int::from_i64(&ctx, x_end);
-  let y_end = z3::ast::Int::from_i64(&ctx, y_end);
+      let inside = Bool::and(&ctx, &[
+        &syms.x_var.ge(&int(bin.x_range.0)),
+        &syms.x_var.le(&int(bin.x_range.1)),
+        &syms.y_var.ge(&int(bin.y_range.0)),
+        &syms.y_var.le(&int(bin.y_range.1)),
+      ]);
 
-  musician_symbol_map.values().for_each(|syms| {
-    optimize.assert(&syms.x_var.clone().ge(&x_start));
-    optimize.assert(&syms.y_var.clone().ge(&y_start));
+      // bins don't overlap, so at most one of a musician's bins can hold
+      optimize.assert(&in_bin.implies(&inside));
+      terms.push(in_bin.ite(&int(bin_scores[inst.0][b].max(0)), &zero));
+    }
+  }
 
-    optimize.assert(&syms.x_var.clone().le(&x_end));
-    optimize.assert(&syms.y_var.clone().le(&y_end));
-  });
+  let total_score = Int::add(&ctx, &terms.iter().collect::<Vec<_>>());
 
+  optimize.maximize(&total_score);
 
-  let _total_score_symbol = Symbol::String("TotalScore".into());
+  // on timeout z3 reports unknown but still keeps the best model found so far
+  let result = optimize.check(&[]);
+  let model = optimize.get_model()?;
 
- // let total_score = Float::new_const(&ctx, total_score_symbol);
+  println!("z3 result: {:?}, approximate score: {:?}", result,
+    model.eval(&total_score, true).and_then(|score| score.as_i64()));
 
-  let sum_of_scores = scores.into_iter().reduce(|a, _b| a ).unwrap().clone();
+  musician_symbols.iter().enumerate().map(|(id, syms)| {
+    let x = model.eval(&syms.x_var, true).and_then(|i| i.as_i64())? as f32;
+    let y = model.eval(&syms.y_var, true).and_then(|i| i.as_i64())? as f32;
 
- // dbg!(optimize.check(&[total_score._eq(&sum_of_scores)]));
+    Some((MusicianId(id), Position { x, y }))
+  }).collect()
+}
 
-  optimize.maximize(&sum_of_scores);
+#[cfg(test)]
+mod tests {
+  use crate::optimizer::to_solution;
+  use super::*;
 
-  let model = optimize.get_model().unwrap();
+  const PROBLEM_JSON: &str = include_str!("../../problems/problem-42.json");
 
-  musician_symbol_map.iter().map(|(&id, syms)| {
-    let x = model.eval(&syms.x_var, true).and_then(|i| i.as_i64()).unwrap_or_default() as f32;
-    let y = model.eval(&syms.y_var, true).and_then(|i| i.as_i64()).unwrap_or_default() as f32;
+  #[test]
+  fn places_valid_musicians() {
+    let problem: ProblemSpec = serde_json::from_str(PROBLEM_JSON).unwrap();
+    let placements = optimize(&problem, Z3Options { timeout_ms: Some(5_000), bins: 4 }).unwrap();
 
-    (id, Position { x, y })
-  }).collect()
+    assert_eq!(placements.len(), problem.musicians.len());
+    assert!(validation::validate(&problem, &to_solution(&placements)).is_ok());
+  }
 }
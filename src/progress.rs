@@ -0,0 +1,26 @@
+use std::sync::OnceLock;
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+
+static MULTI: OnceLock<MultiProgress> = OnceLock::new();
+
+/// Every bar is drawn through one MultiProgress so bars from parallel optimizer runs don't trample each other
+pub fn multi() -> &'static MultiProgress {
+  MULTI.get_or_init(MultiProgress::new)
+}
+
+pub fn bar(len: u64) -> ProgressBar {
+  let sty = ProgressStyle::with_template(
+    "{spinner:.green} [{elapsed_precise}] {wide_bar:.cyan/blue} {pos:>3}/{len} {msg}",
+  )
+    .unwrap()
+    .progress_chars("#>-");
+
+  multi().add(ProgressBar::new(len).with_style(sty))
+}
+
+pub fn spinner(message: &'static str) -> ProgressBar {
+  let pb = multi().add(ProgressBar::new_spinner());
+  pb.set_message(message);
+  pb.enable_steady_tick(std::time::Duration::from_millis(100));
+  pb
+}
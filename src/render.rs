@@ -1,10 +1,23 @@
-use std::collections::HashMap;
 use bevy::{prelude::*, sprite::MaterialMesh2dBundle};
 use bevy::app::CoreSet::Update;
 use bevy::input::common_conditions::*;
+use std::path::PathBuf;
+use bevy::ecs::system::SystemParam;
 use bevy::input::mouse::{MouseMotion, MouseWheel};
-use crate::models::{Position, ProblemSpec};
-use crate::optimizer::MusicianId;
+use bevy::window::PrimaryWindow;
+use crossbeam::channel::Receiver;
+use crate::models::{Position, ProblemSpec, Solution};
+use crate::optimizer::grid::Packing;
+use crate::optimizer::Snapshot;
+use crate::{optimizer, validation};
+use crate::scoring::Score;
+
+mod diff;
+mod edit;
+mod field;
+mod hud;
+mod live;
+mod occlusion;
 
 #[derive(Component)]
 struct Camera;
@@ -15,11 +28,67 @@ struct Room;
 #[derive(Component)]
 struct Stage;
 
+#[derive(Component)]
+struct Pillar;
+
+/// Index of the attendee an entity draws
+#[derive(Component)]
+struct Attendee(usize);
+
+/// Index of the musician an entity draws
+#[derive(Component)]
+struct Musician(usize);
+
+/// Where the cursor points in room coordinates
+#[derive(SystemParam)]
+struct Cursor<'w, 's> {
+  windows: Query<'w, 's, &'static Window, With<PrimaryWindow>>,
+  cameras: Query<'w, 's, (&'static bevy::render::camera::Camera, &'static GlobalTransform), With<Camera>>,
+}
+
+impl Cursor<'_, '_> {
+  fn world_position(&self) -> Option<Vec2> {
+    let (camera, transform) = self.cameras.get_single().ok()?;
+    let cursor = self.windows.get_single().ok()?.cursor_position()?;
+
+    camera.viewport_to_world_2d(transform, cursor)
+  }
+}
+
+/// Current musician positions, ordered by musician index
+fn placements<'a>(musicians: impl Iterator<Item = (&'a Musician, &'a Transform)>) -> Vec<Position> {
+  let mut placements: Vec<(usize, Position)> = musicians
+    .map(|(musician, transform)| (musician.0, Position { x: transform.translation.x, y: transform.translation.y }))
+    .collect();
+
+  placements.sort_by_key(|&(idx, _)| idx);
+  placements.into_iter().map(|(_, pos)| pos).collect()
+}
+
 #[derive(Resource)]
 struct Problem(ProblemSpec);
 
+/// Id parsed from the problem's file name, if it has one
+#[derive(Resource)]
+struct ProblemId(Option<u32>);
+
 #[derive(Resource)]
-struct ASolution(Option<HashMap<MusicianId, Position>>);
+struct ASolution(Option<Solution>);
+
+/// Score of the layout as shown, used to colour attendees
+#[derive(Resource)]
+struct Happiness(Option<Score>);
+
+/// Instrument the attendee heatmap and stage potential overlay show, cycled with tab
+#[derive(Resource)]
+struct SelectedInstrument(usize);
+
+/// What attendees are coloured by, toggled with H
+#[derive(Resource, Copy, Clone, Debug, PartialEq, Eq)]
+enum Heatmap {
+  Total,
+  Instrument,
+}
 
 fn setup(
   problem: Res<Problem>,
@@ -89,16 +158,26 @@ fn setup(
       });
   });
 
-  let red_color = materials.add(ColorMaterial::from(Color::RED));
-
-  for attendee in problem.attendees.iter() {
+  for (idx, attendee) in problem.attendees.iter().enumerate() {
     // println!("Adding attendee: {:?}", attendee);
-    commands.spawn(MaterialMesh2dBundle {
+    commands.spawn((MaterialMesh2dBundle {
       mesh: meshes.add(shape::RegularPolygon::new(1., 6).into()).into(),
-      material: red_color.clone(),
+      // each attendee gets its own material so the heatmap can recolour it
+      material: materials.add(ColorMaterial::from(Color::RED)),
       transform: Transform::from_translation(Vec3::new(attendee.position.x, attendee.position.y, 0.3)),
       ..default()
-    });
+    }, Attendee(idx)));
+  }
+
+  let pillar_color = materials.add(ColorMaterial::from(Color::DARK_GRAY));
+
+  for pillar in problem.pillars.iter() {
+    commands.spawn((MaterialMesh2dBundle {
+      mesh: meshes.add(shape::Circle::new(pillar.radius).into()).into(),
+      material: pillar_color.clone(),
+      transform: Transform::from_translation(Vec3::new(pillar.center[0], pillar.center[1], 0.2)),
+      ..default()
+    }, Pillar));
   }
 
   commands.spawn(MaterialMesh2dBundle {
@@ -110,46 +189,104 @@ fn setup(
 
   match &solution.0 {
     Some(solution) => {
-      for (idx, pos) in solution {
-        let instrument = problem.musicians[idx.0];
-        let color = colorous::RAINBOW.eval_rational(instrument.0, max_inst);
+      for (idx, pos) in solution.placements.iter().enumerate() {
+        let instrument = problem.musicians[idx];
+        let color = instrument_color(instrument.0, max_inst);
         let x = pos.x;
         let y = pos.y;
 
-        commands.spawn(MaterialMesh2dBundle {
+        commands.spawn((MaterialMesh2dBundle {
           mesh: meshes.add(shape::Circle::new(5.0).into()).into(),
-          material: materials.add(ColorMaterial::from(Color::rgb_u8(color.r, color.g, color.b))),
+          material: materials.add(ColorMaterial::from(color)),
           transform: Transform::from_translation(Vec3::new(x, y, 10.0)),
           ..default()
-        });
+        }, Musician(idx), edit::InstrumentColor(color)));
       }
     },
     _ => {
-      for (idx, inst) in problem.musicians.iter().enumerate() {
-        let color = colorous::RAINBOW.eval_rational(inst.0, max_inst);
-
-        let x_start = problem.stage_bottom_left[0] + 10.0;
-        let y_start = problem.stage_bottom_left[1] + 10.0;
-
-        let x_step = 10.0f32;
-        let y_step = 10.0f32;
-
-        let items_per_row = (problem.stage_width/10.0).floor() as usize - 1;
+      // musicians still have to be shown when the stage is too small, even if they overlap
+      let layout = optimizer::grid::layout(problem, Packing::Hex).unwrap_or_else(|| {
+        eprintln!("Stage can't fit all {} musicians", problem.musicians.len());
+        let (bottom_left, _) = validation::stage_bounds(problem);
+        vec![bottom_left; problem.musicians.len()]
+      });
 
-        let x = x_step * ((idx % items_per_row) as f32);
-        let y = y_step * ((idx / items_per_row) as f32);
+      for (idx, (inst, pos)) in problem.musicians.iter().zip(layout).enumerate() {
+        let color = instrument_color(inst.0, max_inst);
 
-        commands.spawn(MaterialMesh2dBundle {
+        commands.spawn((MaterialMesh2dBundle {
           mesh: meshes.add(shape::Circle::new(5.0).into()).into(),
-          material: materials.add(ColorMaterial::from(Color::rgb_u8(color.r, color.g, color.b))),
-          transform: Transform::from_translation(Vec3::new(x_start + x, y_start + y, 10.0)),
+          material: materials.add(ColorMaterial::from(color)),
+          transform: Transform::from_translation(Vec3::new(pos.x, pos.y, 10.0)),
           ..default()
-        });
+        }, Musician(idx), edit::InstrumentColor(color)));
       }
     }
   }
 }
 
+/// Colour musicians playing `instrument` are drawn with, spread over the rainbow
+fn instrument_color(instrument: usize, instruments: usize) -> Color {
+  let color = colorous::RAINBOW.eval_rational(instrument, instruments);
+
+  Color::rgb_u8(color.r, color.g, color.b)
+}
+
+/// Maps a value in -max..=max onto a diverging scale, negative red and positive blue.
+/// The square root keeps the many small values (like attendees far from the stage) from all looking neutral.
+fn diverging_color(value: i64, max: i64) -> Color {
+  let ratio = value as f64 / max.max(1) as f64;
+  let color = colorous::RED_BLUE.eval_continuous(0.5 + 0.5 * ratio.signum() * ratio.abs().sqrt());
+
+  Color::rgb_u8(color.r, color.g, color.b)
+}
+
+fn toggle_heatmap(
+  keys: Res<Input<KeyCode>>,
+  problem: Res<Problem>,
+  mut heatmap: ResMut<Heatmap>,
+  mut selected: ResMut<SelectedInstrument>,
+) {
+  if keys.just_pressed(KeyCode::H) {
+    *heatmap = match *heatmap {
+      Heatmap::Total => Heatmap::Instrument,
+      Heatmap::Instrument => Heatmap::Total,
+    };
+  }
+
+  if keys.just_pressed(KeyCode::Tab) {
+    let instruments = problem.0.attendees.first().map_or(1, |a| a.tastes.len());
+    selected.0 = (selected.0 + 1) % instruments;
+  }
+}
+
+fn color_attendees(
+  heatmap: Res<Heatmap>,
+  selected: Res<SelectedInstrument>,
+  happiness: Res<Happiness>,
+  attendees: Query<(&Attendee, &Handle<ColorMaterial>)>,
+  mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+  if !heatmap.is_changed() && !selected.is_changed() && !happiness.is_changed() {
+    return
+  }
+
+  let Some(score) = &happiness.0 else { return };
+
+  let values = match *heatmap {
+    Heatmap::Total => &score.attendees,
+    Heatmap::Instrument => &score.instruments[selected.0],
+  };
+
+  let max = values.iter().map(|happiness| happiness.abs()).max().unwrap_or(1);
+
+  for (attendee, handle) in attendees.iter() {
+    if let Some(material) = materials.get_mut(handle) {
+      material.color = diverging_color(values[attendee.0], max);
+    }
+  }
+}
+
 //https://bevy-cheatbook.github.io/input/mouse.html
 // https://bevy-cheatbook.github.io/features/camera.html
 fn zoom_camera(
@@ -179,7 +316,14 @@ fn move_camera(
   projection_query: Query<&mut OrthographicProjection, With<Camera>>,
   mut q: Query<&mut Transform, With<Camera>>,
   mut motion_evr: EventReader<MouseMotion>,
+  dragging: Res<edit::Dragging>,
 ) {
+  // left drag moves the musician under the cursor instead of the view
+  if dragging.0.is_some() {
+    motion_evr.clear();
+    return
+  }
+
   let projection = projection_query.single();
 
   let mut transform = q.single_mut();
@@ -190,25 +334,80 @@ fn move_camera(
   }
 }
 
-pub(crate) fn run_app(problem_spec: ProblemSpec, solution: Option<HashMap<MusicianId, Position>>) {
-  App::new()
+/// Shows a problem and optionally a solution. Musicians can be dragged around and S saves the layout to `save_path`.
+/// With a `baseline` its musicians are drawn faded, with arrows to where the shown solution moved them and score deltas.
+pub(crate) fn run_app(problem_id: Option<u32>, problem_spec: ProblemSpec, solution: Option<Solution>, baseline: Option<Solution>, save_path: PathBuf) {
+  let baseline = baseline.map(|baseline| diff::Baseline::new(&problem_spec, baseline));
+  let mut app = build_app(problem_id, problem_spec, solution, save_path);
+
+  if let Some(baseline) = baseline {
+    app
+      .insert_resource(baseline)
+      .add_startup_system(diff::spawn_baseline)
+      .add_system(diff::draw_diff.after(edit::rescore));
+  }
+
+  app.run();
+}
+
+/// Renders the problem while an optimizer on another thread streams its progress through `feed`.
+/// Musicians start in the hex grid layout and glide to each placement as it arrives.
+pub(crate) fn run_live(problem_id: Option<u32>, problem_spec: ProblemSpec, feed: Receiver<Snapshot>, save_path: PathBuf) {
+  build_app(problem_id, problem_spec, None, save_path)
+    .insert_resource(live::LiveFeed::new(feed))
+    .add_startup_system(live::spawn_plot)
+    .add_system(live::receive)
+    .add_system(live::animate.after(live::receive).before(edit::drag_musician))
+    .run();
+}
+
+fn build_app(problem_id: Option<u32>, problem_spec: ProblemSpec, solution: Option<Solution>, save_path: PathBuf) -> App {
+  let mut app = App::new();
+
+  app
     // Background Color
     // https://bevy-cheatbook.github.io/window/clear-color.html
     .insert_resource(ClearColor(Color::GRAY))
     .insert_resource(Problem(problem_spec))
+    .insert_resource(ProblemId(problem_id))
     .insert_resource(ASolution(solution))
+    .insert_resource(Happiness(None))
+    .insert_resource(edit::SavePath(save_path))
+    .init_resource::<edit::Dragging>()
+    .init_resource::<edit::LiveScore>()
+    .insert_resource(SelectedInstrument(0))
+    .insert_resource(Heatmap::Total)
+    .init_resource::<field::FieldOverlay>()
+    .init_resource::<occlusion::SelectedMusician>()
     .add_plugins(DefaultPlugins)
     .add_startup_system(setup)
+    .add_startup_system(field::spawn_field)
+    .add_startup_system(hud::spawn_hud)
+    .add_system(edit::drag_musician)
+    .add_system(edit::highlight_violations.after(edit::drag_musician))
+    .add_system(edit::rescore.after(edit::highlight_violations))
+    .add_system(edit::save_layout)
+    .add_system(toggle_heatmap)
+    .add_system(color_attendees.after(toggle_heatmap).after(edit::rescore))
+    .add_system(field::toggle_field)
+    .add_system(field::update_field.after(field::toggle_field).after(toggle_heatmap))
+    .add_system(occlusion::pick_musician)
+    .add_system(occlusion::draw_occlusion.after(occlusion::pick_musician))
+    .add_system(hud::update_score.after(edit::rescore))
+    .add_system(hud::update_cursor)
+    .add_system(hud::update_heatmap.after(toggle_heatmap))
     .add_system(zoom_camera)
     .add_system(
       move_camera
+        .after(edit::drag_musician)
         .in_base_set(Update)
         .run_if(input_pressed(MouseButton::Left))
-    )
+    );
     // .add_system(
     //   debug_globaltransform
     //     .in_base_set(CoreSet::PostUpdate)
     //     .after(TransformSystem::TransformPropagate)
     // )
-    .run();
+
+  app
 }
@@ -0,0 +1,148 @@
+use bevy::{prelude::*, sprite::MaterialMesh2dBundle};
+use crate::models::{Position, ProblemSpec, Solution};
+use crate::scoring::{self, Score, MUSICIAN_RADIUS};
+use super::{placements, Happiness, Musician, Problem};
+
+const ARROW_WIDTH: f32 = 1.0;
+
+/// Musicians closer than this to where they were in the baseline count as not moved
+const MOVE_TOLERANCE: f32 = 0.01;
+
+/// Solution the shown one is compared against, scored once up front
+#[derive(Resource)]
+pub(super) struct Baseline {
+  placements: Vec<Position>,
+  score: Score,
+}
+
+impl Baseline {
+  pub(super) fn new(problem: &ProblemSpec, solution: Solution) -> Self {
+    Baseline {
+      score: scoring::score(problem, &solution),
+      placements: solution.placements,
+    }
+  }
+}
+
+/// Arrows and labels, respawned whenever the shown layout is rescored
+#[derive(Component)]
+pub(super) struct DiffMark;
+
+#[derive(Component)]
+pub(super) struct DiffTotal;
+
+/// Draws the baseline's musicians faded out under the shown ones, along with the score comparison
+pub(super) fn spawn_baseline(
+  mut commands: Commands,
+  problem: Res<Problem>,
+  baseline: Res<Baseline>,
+  mut meshes: ResMut<Assets<Mesh>>,
+  mut materials: ResMut<Assets<ColorMaterial>>,
+  asset_server: Res<AssetServer>,
+) {
+  let ghost = meshes.add(shape::Circle::new(5.0).into());
+  let material = materials.add(ColorMaterial::from(Color::rgba(1.0, 1.0, 1.0, 0.3)));
+
+  for pos in baseline.placements.iter().take(problem.0.musicians.len()) {
+    commands.spawn(MaterialMesh2dBundle {
+      mesh: ghost.clone().into(),
+      material: material.clone(),
+      transform: Transform::from_xyz(pos.x, pos.y, 3.0),
+      ..default()
+    });
+  }
+
+  commands.spawn((TextBundle::from_section("", TextStyle {
+    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+    font_size: 24.0,
+    color: Color::WHITE,
+  }).with_style(Style {
+    position_type: PositionType::Absolute,
+    position: UiRect { right: Val::Px(10.0), top: Val::Px(10.0), ..default() },
+    ..default()
+  }), DiffTotal));
+}
+
+fn delta_color(delta: i64) -> Color {
+  match delta.signum() {
+    1 => Color::CYAN,
+    -1 => Color::ORANGE_RED,
+    _ => Color::GRAY,
+  }
+}
+
+fn line(from: Vec2, to: Vec2, color: Color, z: f32) -> SpriteBundle {
+  let delta = to - from;
+
+  SpriteBundle {
+    sprite: Sprite {
+      color,
+      custom_size: Some(Vec2::new(delta.length(), ARROW_WIDTH)),
+      ..default()
+    },
+    transform: Transform::from_translation(((from + to) / 2.0).extend(z))
+      .with_rotation(Quat::from_rotation_z(delta.y.atan2(delta.x))),
+    ..default()
+  }
+}
+
+/// Arrows from each moved musician's baseline position and score deltas per musician and in total
+pub(super) fn draw_diff(
+  mut commands: Commands,
+  baseline: Res<Baseline>,
+  happiness: Res<Happiness>,
+  musicians: Query<(&Musician, &Transform)>,
+  marks: Query<Entity, With<DiffMark>>,
+  mut total: Query<&mut Text, With<DiffTotal>>,
+  asset_server: Res<AssetServer>,
+) {
+  if !happiness.is_changed() {
+    return
+  }
+
+  let Some(score) = &happiness.0 else { return };
+
+  for mark in marks.iter() {
+    commands.entity(mark).despawn_recursive();
+  }
+
+  let delta = score.total - baseline.score.total;
+
+  if let Ok(mut total) = total.get_single_mut() {
+    total.sections[0].value = format!("{} vs baseline {} ({:+})", score.total, baseline.score.total, delta);
+    total.sections[0].style.color = delta_color(delta);
+  }
+
+  let style = TextStyle {
+    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+    font_size: 14.0,
+    color: Color::WHITE,
+  };
+
+  let current = placements(musicians.iter());
+
+  for (musician, (&now, &before)) in current.iter().zip(baseline.placements.iter()).enumerate() {
+    let (from, to) = (Vec2::new(before.x, before.y), Vec2::new(now.x, now.y));
+    let delta = score.musicians[musician] - baseline.score.musicians[musician];
+    let color = delta_color(delta);
+
+    if from.distance(to) > MOVE_TOLERANCE {
+      commands.spawn((line(from, to, color, 4.0), DiffMark));
+
+      // arrow head, two short strokes folded back from the tip
+      let back = (from - to).normalize() * MUSICIAN_RADIUS as f32;
+      let tip = to + back;
+      for angle in [0.5f32, -0.5] {
+        commands.spawn((line(tip, tip + Vec2::from_angle(angle).rotate(back), color, 4.0), DiffMark));
+      }
+    }
+
+    if delta != 0 {
+      commands.spawn((Text2dBundle {
+        text: Text::from_section(format!("{:+}", delta), TextStyle { color, ..style.clone() }),
+        transform: Transform::from_xyz(to.x, to.y - 1.5 * MUSICIAN_RADIUS as f32, 11.0),
+        ..default()
+      }, DiffMark));
+    }
+  }
+}
@@ -0,0 +1,154 @@
+use std::collections::HashSet;
+use std::path::PathBuf;
+use bevy::prelude::*;
+use crate::models::Solution;
+use crate::scoring::{self, MUSICIAN_RADIUS};
+use crate::validation;
+use super::{placements, ASolution, Cursor, Happiness, Musician, Problem};
+
+/// Musicians breaking a placement constraint are drawn in this colour
+const VIOLATION_COLOR: Color = Color::FUCHSIA;
+
+/// Musician being dragged with the left mouse button
+#[derive(Resource, Default)]
+pub(super) struct Dragging(pub(super) Option<usize>);
+
+/// Colour a musician is drawn with while it breaks no constraint
+#[derive(Component)]
+pub(super) struct InstrumentColor(pub(super) Color);
+
+/// Where S writes the edited layout
+#[derive(Resource)]
+pub(super) struct SavePath(pub(super) PathBuf);
+
+/// Exact score of the layout as edited. Rescoring is throttled since it can take a while and
+/// musicians move every frame while dragged or animated.
+#[derive(Resource)]
+pub(super) struct LiveScore {
+  pub(super) total: Option<i64>,
+  pub(super) violations: usize,
+  dirty: bool,
+  throttle: Timer,
+}
+
+impl Default for LiveScore {
+  fn default() -> Self {
+    LiveScore {
+      total: None,
+      violations: 0,
+      dirty: true,
+      throttle: Timer::from_seconds(0.25, TimerMode::Repeating),
+    }
+  }
+}
+
+/// Layout as currently shown, keeping the loaded volumes
+pub(super) fn current_solution(solution: &ASolution, musicians: &Query<(&Musician, &Transform)>) -> Solution {
+  Solution {
+    placements: placements(musicians.iter()),
+    volumes: solution.0.as_ref().and_then(|solution| solution.volumes.clone()),
+  }
+}
+
+pub(super) fn drag_musician(
+  buttons: Res<Input<MouseButton>>,
+  cursor: Cursor,
+  mut dragging: ResMut<Dragging>,
+  mut musicians: Query<(&Musician, &mut Transform)>,
+) {
+  // released outside the window, where there's no cursor position, still drops the musician
+  if buttons.just_released(MouseButton::Left) {
+    dragging.0 = None;
+  }
+
+  let Some(cursor) = cursor.world_position() else { return };
+
+  if buttons.just_pressed(MouseButton::Left) {
+    dragging.0 = musicians.iter()
+      .find(|(_, transform)| transform.translation.truncate().distance(cursor) <= MUSICIAN_RADIUS as f32)
+      .map(|(musician, _)| musician.0);
+  }
+
+  let Some(dragged) = dragging.0 else { return };
+
+  for (musician, mut transform) in musicians.iter_mut() {
+    if musician.0 == dragged && transform.translation.truncate() != cursor {
+      transform.translation.x = cursor.x;
+      transform.translation.y = cursor.y;
+    }
+  }
+}
+
+pub(super) fn highlight_violations(
+  problem: Res<Problem>,
+  solution: Res<ASolution>,
+  moved: Query<(), (With<Musician>, Changed<Transform>)>,
+  musicians: Query<(&Musician, &Transform)>,
+  colors: Query<(&Musician, &InstrumentColor, &Handle<ColorMaterial>)>,
+  mut materials: ResMut<Assets<ColorMaterial>>,
+  mut live: ResMut<LiveScore>,
+) {
+  if moved.is_empty() {
+    return
+  }
+
+  let violations = validation::violations(&problem.0, &current_solution(&solution, &musicians));
+  let at_fault: HashSet<usize> = violations.iter()
+    .flat_map(|violation| violation.musicians())
+    .map(|musician| musician.0)
+    .collect();
+
+  for (musician, color, handle) in colors.iter() {
+    if let Some(material) = materials.get_mut(handle) {
+      material.color = if at_fault.contains(&musician.0) { VIOLATION_COLOR } else { color.0 };
+    }
+  }
+
+  live.violations = violations.len();
+  live.dirty = true;
+}
+
+pub(super) fn rescore(
+  time: Res<Time>,
+  problem: Res<Problem>,
+  solution: Res<ASolution>,
+  musicians: Query<(&Musician, &Transform)>,
+  mut live: ResMut<LiveScore>,
+  mut happiness: ResMut<Happiness>,
+) {
+  let throttled = !live.throttle.tick(time.delta()).just_finished();
+
+  if !live.dirty || throttled {
+    return
+  }
+
+  let score = scoring::score(&problem.0, &current_solution(&solution, &musicians));
+
+  live.total = Some(score.total);
+  live.dirty = false;
+  happiness.0 = Some(score);
+}
+
+pub(super) fn save_layout(
+  keys: Res<Input<KeyCode>>,
+  path: Res<SavePath>,
+  solution: Res<ASolution>,
+  live: Res<LiveScore>,
+  musicians: Query<(&Musician, &Transform)>,
+) {
+  if !keys.just_pressed(KeyCode::S) {
+    return
+  }
+
+  let result = serde_json::to_vec(&current_solution(&solution, &musicians))
+    .map_err(anyhow::Error::from)
+    .and_then(|json| Ok(std::fs::write(&path.0, json)?));
+
+  match result {
+    Ok(()) => {
+      let score = live.total.map_or("unscored".to_string(), |total| format!("score {}", total));
+      println!("Saved layout to {} ({}, {} violations)", path.0.display(), score, live.violations);
+    }
+    Err(err) => eprintln!("Saving {} failed: {:#}", path.0.display(), err),
+  }
+}
@@ -0,0 +1,94 @@
+use bevy::prelude::*;
+use crate::models::{Instrument, Position};
+use crate::scoring;
+use super::{diverging_color, placements, Happiness, Musician, Problem, SelectedInstrument};
+
+/// Cells along the longest side of the stage
+const FIELD_RESOLUTION: usize = 48;
+
+/// One cell of the stage potential overlay, sampled at its center
+#[derive(Component)]
+pub(super) struct FieldCell(Position);
+
+/// Overlay of Σ taste/d² across the stage for the selected instrument. P shows it, B toggles
+/// blocking by the musicians where they are now, which is much slower on large problems.
+#[derive(Resource, Default)]
+pub(super) struct FieldOverlay {
+  visible: bool,
+  blocking: bool,
+}
+
+pub(super) fn spawn_field(problem: Res<Problem>, mut commands: Commands) {
+  let problem = &problem.0;
+  let cell = problem.stage_width.max(problem.stage_height) / FIELD_RESOLUTION as f32;
+
+  let columns = (problem.stage_width / cell).ceil() as usize;
+  let rows = (problem.stage_height / cell).ceil() as usize;
+
+  for row in 0..rows {
+    for column in 0..columns {
+      let center = Position {
+        x: problem.stage_bottom_left[0] + cell * (column as f32 + 0.5),
+        y: problem.stage_bottom_left[1] + cell * (row as f32 + 0.5),
+      };
+
+      commands.spawn((SpriteBundle {
+        sprite: Sprite {
+          custom_size: Some(Vec2::splat(cell)),
+          ..default()
+        },
+        // above the stage, below pillars and attendees
+        transform: Transform::from_xyz(center.x, center.y, 0.15),
+        visibility: Visibility::Hidden,
+        ..default()
+      }, FieldCell(center)));
+    }
+  }
+}
+
+pub(super) fn toggle_field(keys: Res<Input<KeyCode>>, mut overlay: ResMut<FieldOverlay>) {
+  if keys.just_pressed(KeyCode::P) {
+    overlay.visible = !overlay.visible;
+  }
+
+  if keys.just_pressed(KeyCode::B) {
+    overlay.blocking = !overlay.blocking;
+  }
+}
+
+pub(super) fn update_field(
+  problem: Res<Problem>,
+  overlay: Res<FieldOverlay>,
+  selected: Res<SelectedInstrument>,
+  happiness: Res<Happiness>,
+  musicians: Query<(&Musician, &Transform)>,
+  mut cells: Query<(&FieldCell, &mut Sprite, &mut Visibility)>,
+) {
+  // moved musicians are rescored on a throttle, which is also often enough to redo the blocking
+  let moved = overlay.blocking && happiness.is_changed();
+
+  if !overlay.is_changed() && (!overlay.visible || !(selected.is_changed() || moved)) {
+    return
+  }
+
+  if !overlay.visible {
+    for (_, _, mut visibility) in cells.iter_mut() {
+      *visibility = Visibility::Hidden;
+    }
+
+    return
+  }
+
+  let occluders: Vec<Position> = if overlay.blocking { placements(musicians.iter()) } else { Vec::new() };
+
+  let potentials: Vec<f64> = cells.iter()
+    .map(|(cell, _, _)| scoring::potential(&problem.0, Instrument(selected.0), cell.0, &occluders))
+    .collect();
+
+  let max = potentials.iter().map(|potential| potential.abs()).fold(0.0, f64::max);
+
+  for ((_, mut sprite, mut visibility), potential) in cells.iter_mut().zip(potentials) {
+    sprite.color = diverging_color(potential as i64, max as i64);
+    *visibility = Visibility::Visible;
+  }
+}
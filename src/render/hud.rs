@@ -0,0 +1,135 @@
+use bevy::prelude::*;
+use crate::scoring::{self, Extensions};
+use super::edit::current_solution;
+use super::{instrument_color, ASolution, Cursor, Happiness, Heatmap, Musician, Problem, ProblemId, SelectedInstrument};
+
+const FONT_SIZE: f32 = 16.0;
+
+#[derive(Component)]
+pub(super) struct HudScore;
+
+#[derive(Component)]
+pub(super) struct HudCursor;
+
+#[derive(Component)]
+pub(super) struct HudHeatmap;
+
+/// Panel in the top left corner with what is being looked at: problem, score, heatmap, instruments and cursor position
+pub(super) fn spawn_hud(
+  mut commands: Commands,
+  problem_id: Res<ProblemId>,
+  problem: Res<Problem>,
+  asset_server: Res<AssetServer>,
+) {
+  let problem = &problem.0;
+  let style = TextStyle {
+    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+    font_size: FONT_SIZE,
+    color: Color::WHITE,
+  };
+
+  let title = match problem_id.0 {
+    Some(id) => format!("Problem {}", id),
+    None => "Problem".to_string(),
+  };
+
+  let instruments = problem.musicians.iter().max().map_or(0, |inst| inst.0 + 1);
+
+  commands.spawn(NodeBundle {
+    style: Style {
+      position_type: PositionType::Absolute,
+      position: UiRect { left: Val::Px(10.0), top: Val::Px(10.0), ..default() },
+      flex_direction: FlexDirection::Column,
+      padding: UiRect::all(Val::Px(6.0)),
+      ..default()
+    },
+    background_color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+    ..default()
+  }).with_children(|hud| {
+    hud.spawn(TextBundle::from_section(format!(
+      "{}: {} musicians, {} attendees, {} pillars",
+      title, problem.musicians.len(), problem.attendees.len(), problem.pillars.len(),
+    ), style.clone()));
+    hud.spawn((TextBundle::from_section("Scoring...", style.clone()), HudScore));
+    hud.spawn((TextBundle::from_section("", style.clone()), HudHeatmap));
+    hud.spawn((TextBundle::from_section("", style.clone()), HudCursor));
+
+    for instrument in 0..instruments {
+      let players = problem.musicians.iter().filter(|inst| inst.0 == instrument).count();
+
+      hud.spawn(NodeBundle {
+        style: Style {
+          align_items: AlignItems::Center,
+          ..default()
+        },
+        ..default()
+      }).with_children(|row| {
+        row.spawn(NodeBundle {
+          style: Style {
+            size: Size::new(Val::Px(FONT_SIZE * 0.75), Val::Px(FONT_SIZE * 0.75)),
+            margin: UiRect::right(Val::Px(4.0)),
+            ..default()
+          },
+          background_color: instrument_color(instrument, instruments).into(),
+          ..default()
+        });
+        row.spawn(TextBundle::from_section(format!("Instrument {} ({} musicians)", instrument, players), style.clone()));
+      });
+    }
+  });
+}
+
+/// Shows the latest score, and the score without the playing together extension when the problem uses it
+pub(super) fn update_score(
+  problem: Res<Problem>,
+  solution: Res<ASolution>,
+  happiness: Res<Happiness>,
+  musicians: Query<(&Musician, &Transform)>,
+  mut text: Query<&mut Text, With<HudScore>>,
+) {
+  if !happiness.is_changed() {
+    return
+  }
+
+  let Some(score) = &happiness.0 else { return };
+  let Ok(mut text) = text.get_single_mut() else { return };
+
+  let problem = &problem.0;
+
+  text.sections[0].value = if Extensions::for_problem(problem).playing_together {
+    let plain = scoring::score_with(problem, &current_solution(&solution, &musicians), Extensions::NONE);
+    format!("Score: {} ({} without extensions)", score.total, plain.total)
+  } else {
+    format!("Score: {}", score.total)
+  };
+}
+
+pub(super) fn update_cursor(cursor: Cursor, mut text: Query<&mut Text, With<HudCursor>>) {
+  let Some(position) = cursor.world_position() else { return };
+  let Ok(mut text) = text.get_single_mut() else { return };
+
+  let value = format!("Cursor: ({:.1}, {:.1})", position.x, position.y);
+
+  // only touch the text when it changes so it isn't re-laid out every frame
+  if text.sections[0].value != value {
+    text.sections[0].value = value;
+  }
+}
+
+/// What attendees are coloured by and which instrument the heatmap and stage potential show
+pub(super) fn update_heatmap(
+  heatmap: Res<Heatmap>,
+  selected: Res<SelectedInstrument>,
+  mut text: Query<&mut Text, With<HudHeatmap>>,
+) {
+  if !heatmap.is_changed() && !selected.is_changed() {
+    return
+  }
+
+  let Ok(mut text) = text.get_single_mut() else { return };
+
+  text.sections[0].value = match *heatmap {
+    Heatmap::Total => format!("Heatmap: total happiness, instrument {} selected", selected.0),
+    Heatmap::Instrument => format!("Heatmap: instrument {} happiness", selected.0),
+  };
+}
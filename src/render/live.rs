@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+use bevy::prelude::*;
+use crossbeam::channel::{Receiver, TryRecvError};
+use crate::models::Position;
+use crate::optimizer::{MusicianId, Snapshot};
+use super::edit::Dragging;
+use super::Musician;
+
+/// Scores kept for the convergence plot
+const PLOT_SAMPLES: usize = 120;
+
+/// How quickly musicians glide towards the latest placement, per second
+const GLIDE_RATE: f32 = 8.0;
+
+/// Snapshots streamed from an optimizer running on another thread
+#[derive(Resource)]
+pub(super) struct LiveFeed {
+  updates: Receiver<Snapshot>,
+  targets: HashMap<MusicianId, Position>,
+  scores: Vec<f64>,
+  exact: Option<i64>,
+  finished: bool,
+}
+
+impl LiveFeed {
+  pub(super) fn new(updates: Receiver<Snapshot>) -> Self {
+    LiveFeed {
+      updates,
+      targets: HashMap::new(),
+      scores: Vec::new(),
+      exact: None,
+      finished: false,
+    }
+  }
+}
+
+#[derive(Component)]
+pub(super) struct ScorePlot;
+
+#[derive(Component)]
+pub(super) struct ScoreLabel;
+
+pub(super) fn spawn_plot(mut commands: Commands, asset_server: Res<AssetServer>) {
+  commands.spawn((NodeBundle {
+    style: Style {
+      position_type: PositionType::Absolute,
+      position: UiRect { left: Val::Px(10.0), bottom: Val::Px(10.0), ..default() },
+      size: Size::new(Val::Px(3.0 * PLOT_SAMPLES as f32), Val::Px(120.0)),
+      align_items: AlignItems::FlexEnd,
+      ..default()
+    },
+    background_color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+    ..default()
+  }, ScorePlot));
+
+  commands.spawn((TextBundle::from_section("Waiting for the optimizer", TextStyle {
+    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+    font_size: 20.0,
+    color: Color::WHITE,
+  }).with_style(Style {
+    position_type: PositionType::Absolute,
+    position: UiRect { left: Val::Px(10.0), bottom: Val::Px(135.0), ..default() },
+    ..default()
+  }), ScoreLabel));
+}
+
+/// Drains the channel, keeping the latest placements, every objective score and the exact final score
+pub(super) fn receive(
+  mut commands: Commands,
+  mut feed: ResMut<LiveFeed>,
+  plot: Query<Entity, With<ScorePlot>>,
+  mut label: Query<&mut Text, With<ScoreLabel>>,
+) {
+  if feed.finished {
+    return
+  }
+
+  let mut received = false;
+
+  loop {
+    match feed.updates.try_recv() {
+      Ok(snapshot) => {
+        feed.targets.extend(snapshot.placements);
+        feed.scores.push(snapshot.score);
+        feed.exact = snapshot.exact.or(feed.exact);
+        received = true;
+      }
+      Err(TryRecvError::Disconnected) => {
+        feed.finished = true;
+        break
+      }
+      Err(TryRecvError::Empty) => break,
+    }
+  }
+
+  if let (Some(score), Ok(mut label)) = (feed.scores.last(), label.get_single_mut()) {
+    let exact = feed.exact.map_or(String::new(), |exact| format!(", exact score {}", exact));
+    let done = if feed.finished { " (done)" } else { "" };
+    label.sections[0].value = format!("Objective {:.0} after {} updates{}{}", score, feed.scores.len(), exact, done);
+  }
+
+  if !received {
+    return
+  }
+
+  let Ok(plot) = plot.get_single() else { return };
+
+  let recent = &feed.scores[feed.scores.len().saturating_sub(PLOT_SAMPLES)..];
+  let min = recent.iter().copied().fold(f64::INFINITY, f64::min);
+  let max = recent.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+
+  commands.entity(plot).despawn_descendants();
+  commands.entity(plot).with_children(|parent| {
+    for &score in recent {
+      let height = if max > min { (score - min) / (max - min) } else { 1.0 };
+
+      parent.spawn(NodeBundle {
+        style: Style {
+          size: Size::new(Val::Px(3.0), Val::Percent(100.0 * height.max(0.02) as f32)),
+          ..default()
+        },
+        background_color: Color::YELLOW_GREEN.into(),
+        ..default()
+      });
+    }
+  });
+}
+
+/// Moves every placed musician part of the way towards its latest position, leaving a dragged one alone
+pub(super) fn animate(
+  time: Res<Time>,
+  feed: Res<LiveFeed>,
+  dragging: Res<Dragging>,
+  mut musicians: Query<(&Musician, &mut Transform)>,
+) {
+  let step = (GLIDE_RATE * time.delta_seconds()).min(1.0);
+
+  for (musician, mut transform) in musicians.iter_mut() {
+    if dragging.0 == Some(musician.0) {
+      continue
+    }
+
+    let Some(target) = feed.targets.get(&MusicianId(musician.0)) else { continue };
+    let target = Vec2::new(target.x, target.y);
+    let current = transform.translation.truncate();
+
+    if current.distance(target) > 0.01 {
+      let next = current.lerp(target, step);
+      transform.translation.x = next.x;
+      transform.translation.y = next.y;
+    }
+  }
+}
@@ -0,0 +1,97 @@
+use bevy::prelude::*;
+use crate::models::Solution;
+use crate::scoring::{self, MUSICIAN_RADIUS};
+use super::{placements, ASolution, Cursor, Musician, Problem};
+
+/// Musician whose lines to every attendee are drawn, picked by clicking it
+#[derive(Resource, Default)]
+pub(super) struct SelectedMusician(pub(super) Option<usize>);
+
+/// Everything drawn for the selected musician, despawned when the selection changes
+#[derive(Component)]
+pub(super) struct OcclusionLine;
+
+const LINE_WIDTH: f32 = 0.5;
+
+pub(super) fn pick_musician(
+  buttons: Res<Input<MouseButton>>,
+  cursor: Cursor,
+  musicians: Query<(&Musician, &Transform)>,
+  mut selected: ResMut<SelectedMusician>,
+) {
+  if !buttons.just_pressed(MouseButton::Left) {
+    return
+  }
+
+  let Some(cursor) = cursor.world_position() else { return };
+
+  let picked = musicians.iter()
+    .find(|(_, transform)| transform.translation.truncate().distance(cursor) <= MUSICIAN_RADIUS as f32)
+    .map(|(musician, _)| musician.0);
+
+  // clicking empty space clears the selection
+  if picked != selected.0 {
+    selected.0 = picked;
+  }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(super) fn draw_occlusion(
+  mut commands: Commands,
+  problem: Res<Problem>,
+  solution: Res<ASolution>,
+  selected: Res<SelectedMusician>,
+  musicians: Query<(&Musician, &Transform)>,
+  moved: Query<(), (With<Musician>, Changed<Transform>)>,
+  lines: Query<Entity, With<OcclusionLine>>,
+  asset_server: Res<AssetServer>,
+) {
+  if !selected.is_changed() && moved.is_empty() {
+    return
+  }
+
+  for line in lines.iter() {
+    commands.entity(line).despawn_recursive();
+  }
+
+  let Some(musician) = selected.0 else { return };
+
+  let problem = &problem.0;
+  let current = Solution {
+    placements: placements(musicians.iter()),
+    volumes: solution.0.as_ref().and_then(|solution| solution.volumes.clone()),
+  };
+
+  let pos = current.placements[musician];
+  let from = Vec2::new(pos.x, pos.y);
+
+  for attendee in problem.attendees.iter() {
+    let clear = scoring::line_of_sight(problem, &current.placements, musician, attendee.position);
+    let to = Vec2::new(attendee.position.x, attendee.position.y);
+    let delta = to - from;
+
+    commands.spawn((SpriteBundle {
+      sprite: Sprite {
+        color: if clear { Color::rgba(0.0, 1.0, 0.0, 0.6) } else { Color::rgba(0.5, 0.5, 0.5, 0.6) },
+        custom_size: Some(Vec2::new(delta.length(), LINE_WIDTH)),
+        ..default()
+      },
+      transform: Transform::from_translation(((from + to) / 2.0).extend(5.0))
+        .with_rotation(Quat::from_rotation_z(delta.y.atan2(delta.x))),
+      ..default()
+    }, OcclusionLine));
+  }
+
+  let contribution = scoring::contribution(problem, &current, musician);
+  println!("Musician {} ({:?}) at ({}, {}) contributes {}", musician, problem.musicians[musician], pos.x, pos.y, contribution);
+
+  commands.spawn((Text2dBundle {
+    text: Text::from_section(format!("#{} {}", musician, contribution), TextStyle {
+      font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+      font_size: 20.0,
+      color: Color::WHITE,
+    }),
+    transform: Transform::from_xyz(pos.x, pos.y + 2.0 * MUSICIAN_RADIUS as f32, 11.0),
+    ..default()
+  }, OcclusionLine));
+}
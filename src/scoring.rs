@@ -0,0 +1,278 @@
+use crate::models::{Instrument, Position, ProblemSpec, Solution};
+
+pub const MUSICIAN_RADIUS: f64 = 5.0;
+
+/// Optional rules from the full round that change how happiness is computed
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Extensions {
+  /// Musicians playing the same instrument boost each other by 1 + Σ 1/d
+  pub playing_together: bool,
+}
+
+impl Extensions {
+  pub const NONE: Extensions = Extensions { playing_together: false };
+
+  /// The official rules enable the extensions on every problem with pillars
+  pub fn for_problem(problem: &ProblemSpec) -> Self {
+    Extensions {
+      playing_together: !problem.pillars.is_empty(),
+    }
+  }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Score {
+  pub total: i64,
+  /// Happiness each musician contributes, summed over all attendees
+  pub musicians: Vec<i64>,
+  /// Happiness each attendee receives, summed over all musicians
+  pub attendees: Vec<i64>,
+  /// Happiness each attendee receives from each instrument, indexed `[instrument][attendee]`
+  pub instruments: Vec<Vec<i64>>,
+}
+
+/// Base impact of a musician on an attendee: ceil(1e6 * taste / d²)
+pub fn impact(taste: f64, attendee: Position, musician: Position) -> f64 {
+  (1_000_000f64 * taste / dist_squared(attendee, musician)).ceil()
+}
+
+pub fn dist_squared(p1: Position, p2: Position) -> f64 {
+  let del_x = (p1.x - p2.x) as f64;
+  let del_y = (p1.y - p2.y) as f64;
+
+  del_x * del_x + del_y * del_y
+}
+
+/// True when the segment `from` -> `to` passes strictly within `radius` of `center`
+pub fn segment_blocked(from: Position, to: Position, center: Position, radius: f64) -> bool {
+  let (ax, ay) = (from.x as f64, from.y as f64);
+  let (bx, by) = (to.x as f64, to.y as f64);
+  let (cx, cy) = (center.x as f64, center.y as f64);
+
+  let del_x = bx - ax;
+  let del_y = by - ay;
+  let len_sq = del_x * del_x + del_y * del_y;
+
+  let t = if len_sq == 0.0 {
+    0.0
+  } else {
+    (((cx - ax) * del_x + (cy - ay) * del_y) / len_sq).clamp(0.0, 1.0)
+  };
+
+  let closest_x = ax + t * del_x - cx;
+  let closest_y = ay + t * del_y - cy;
+
+  closest_x * closest_x + closest_y * closest_y < radius * radius
+}
+
+/// Whether `attendee` can hear the musician at index `musician` without any other musician or pillar in the way
+pub fn line_of_sight(problem: &ProblemSpec, placements: &[Position], musician: usize, attendee: Position) -> bool {
+  let pos = placements[musician];
+  let dist = dist_squared(attendee, pos).sqrt();
+  let reach = dist + MUSICIAN_RADIUS;
+
+  let musicians_clear = placements.iter().enumerate()
+    .filter(|&(other, _)| other != musician)
+    // anything further away than the attendee can't be on the segment
+    .filter(|&(_, &other)| dist_squared(pos, other) <= reach * reach)
+    .all(|(_, &other)| !segment_blocked(attendee, pos, other, MUSICIAN_RADIUS));
+
+  musicians_clear && problem.pillars.iter()
+    .all(|pillar| !segment_blocked(attendee, pos, pillar.position(), pillar.radius as f64))
+}
+
+/// Σ impact of an instrument played at `pos` over every attendee that can hear it, before volume and closeness.
+/// Pillars always block, `occluders` block unless they stand exactly at `pos`.
+pub fn potential(problem: &ProblemSpec, inst: Instrument, pos: Position, occluders: &[Position]) -> f64 {
+  problem.attendees.iter()
+    .filter(|attendee| occluders.iter()
+      .all(|&other| other == pos || !segment_blocked(attendee.position, pos, other, MUSICIAN_RADIUS)))
+    .filter(|attendee| problem.pillars.iter()
+      .all(|pillar| !segment_blocked(attendee.position, pos, pillar.position(), pillar.radius as f64)))
+    .map(|attendee| impact(attendee.tastes[inst.0], attendee.position, pos))
+    .sum()
+}
+
+/// Closeness factor of a musician: 1 + Σ 1/d over other musicians playing the same instrument
+pub fn closeness(problem: &ProblemSpec, placements: &[Position], musician: usize) -> f64 {
+  let inst = problem.musicians[musician];
+  let pos = placements[musician];
+
+  1.0 + placements.iter().enumerate()
+    .filter(|&(other, _)| other != musician && problem.musicians[other] == inst)
+    .map(|(_, &other)| 1.0 / dist_squared(pos, other).sqrt())
+    .sum::<f64>()
+}
+
+pub fn score(problem: &ProblemSpec, solution: &Solution) -> Score {
+  score_with(problem, solution, Extensions::for_problem(problem))
+}
+
+/// What a musician's impact is multiplied by: its volume, and its closeness when playing together.
+/// Musicians past the end of a short volumes list play at the default 1.0, not at full volume, so layouts
+/// being edited still score. [crate::validation::violations] rejects such a list with `VolumeCount`.
+fn factor(problem: &ProblemSpec, solution: &Solution, musician: usize, extensions: Extensions) -> f64 {
+  let volume = solution.volumes.as_ref().and_then(|volumes| volumes.get(musician).copied()).unwrap_or(1.0);
+
+  if extensions.playing_together {
+    volume * closeness(problem, &solution.placements, musician)
+  } else {
+    volume
+  }
+}
+
+/// Happiness a single musician contributes, summed over all attendees
+pub fn contribution(problem: &ProblemSpec, solution: &Solution, musician: usize) -> i64 {
+  let factor = factor(problem, solution, musician, Extensions::for_problem(problem));
+  let pos = solution.placements[musician];
+  let inst = problem.musicians[musician];
+
+  problem.attendees.iter()
+    .filter(|attendee| line_of_sight(problem, &solution.placements, musician, attendee.position))
+    .map(|attendee| (factor * impact(attendee.tastes[inst.0], attendee.position, pos)).ceil() as i64)
+    .sum()
+}
+
+pub fn score_with(problem: &ProblemSpec, solution: &Solution, extensions: Extensions) -> Score {
+  let placements = &solution.placements;
+
+  let factors: Vec<f64> = (0..placements.len())
+    .map(|m_idx| factor(problem, solution, m_idx, extensions))
+    .collect();
+
+  let mut musicians = vec![0i64; placements.len()];
+  let mut attendees = vec![0i64; problem.attendees.len()];
+  let mut instruments = vec![attendees.clone(); problem.attendees.first().map_or(0, |a| a.tastes.len())];
+
+  for (a_idx, attendee) in problem.attendees.iter().enumerate() {
+    for (m_idx, (&pos, inst)) in placements.iter().zip(problem.musicians.iter()).enumerate() {
+      if !line_of_sight(problem, placements, m_idx, attendee.position) {
+        continue
+      }
+
+      let happiness = (factors[m_idx] * impact(attendee.tastes[inst.0], attendee.position, pos)).ceil() as i64;
+
+      musicians[m_idx] += happiness;
+      attendees[a_idx] += happiness;
+      instruments[inst.0][a_idx] += happiness;
+    }
+  }
+
+  Score {
+    total: musicians.iter().sum(),
+    musicians,
+    attendees,
+    instruments,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::models::Pillar;
+  use super::*;
+
+  const PROBLEM_JSON: &str = include_str!("../problems/example.json");
+
+  fn example_solution() -> Solution {
+    Solution {
+      placements: vec![
+        Position { x: 590.0, y: 10.0 },
+        Position { x: 1100.0, y: 100.0 },
+        Position { x: 1100.0, y: 150.0 },
+      ],
+      volumes: None,
+    }
+  }
+
+  #[test]
+  fn scores_example() {
+    let problem: ProblemSpec = serde_json::from_str(PROBLEM_JSON).unwrap();
+    let score = score(&problem, &example_solution());
+
+    assert_eq!(score.total, 5343);
+    assert_eq!(score.musicians.iter().sum::<i64>(), 5343);
+    assert_eq!(score.attendees.iter().sum::<i64>(), 5343);
+    assert_eq!(score.attendees.len(), 3);
+
+    let per_instrument: i64 = score.instruments.iter().flatten().sum();
+    assert_eq!(per_instrument, 5343);
+  }
+
+  #[test]
+  fn scores_example_playing_together() {
+    let problem: ProblemSpec = serde_json::from_str(PROBLEM_JSON).unwrap();
+    let extensions = Extensions { playing_together: true };
+
+    assert_eq!(Extensions::for_problem(&problem), Extensions::NONE);
+    assert_eq!(score_with(&problem, &example_solution(), extensions).total, 5357);
+  }
+
+  #[test]
+  fn potential_matches_lone_musician() {
+    let mut problem: ProblemSpec = serde_json::from_str(PROBLEM_JSON).unwrap();
+    problem.musicians.truncate(1);
+
+    let pos = Position { x: 1100.0, y: 100.0 };
+    let solution = Solution { placements: vec![pos], volumes: None };
+
+    assert_eq!(potential(&problem, Instrument(0), pos, &[]) as i64, score(&problem, &solution).total);
+
+    let blocker = Position { x: 1100.0, y: 150.0 };
+    assert!(potential(&problem, Instrument(0), pos, &[blocker]) < potential(&problem, Instrument(0), pos, &[]));
+  }
+
+  #[test]
+  fn volumes_scale_musicians() {
+    let problem: ProblemSpec = serde_json::from_str(PROBLEM_JSON).unwrap();
+    let base = score(&problem, &example_solution());
+
+    let solution = Solution {
+      volumes: Some(vec![1.0, 0.0, 1.0]),
+      ..example_solution()
+    };
+    let muted = score(&problem, &solution);
+
+    assert_eq!(muted.musicians[1], 0);
+    assert_eq!(muted.total, base.total - base.musicians[1]);
+
+    for musician in 0..3 {
+      assert_eq!(contribution(&problem, &solution, musician), muted.musicians[musician]);
+    }
+  }
+
+  #[test]
+  fn short_volumes_default_to_unit_volume() {
+    let problem: ProblemSpec = serde_json::from_str(PROBLEM_JSON).unwrap();
+    let base = score(&problem, &example_solution());
+
+    let solution = Solution {
+      volumes: Some(vec![1.0]),
+      ..example_solution()
+    };
+
+    assert_eq!(score(&problem, &solution), base);
+  }
+
+  #[test]
+  fn blocks_segments_through_circles() {
+    let from = Position { x: 0.0, y: 0.0 };
+    let to = Position { x: 100.0, y: 0.0 };
+
+    assert!(segment_blocked(from, to, Position { x: 50.0, y: 4.9 }, MUSICIAN_RADIUS));
+    assert!(!segment_blocked(from, to, Position { x: 50.0, y: 5.0 }, MUSICIAN_RADIUS));
+    assert!(!segment_blocked(from, to, Position { x: 110.0, y: 0.0 }, MUSICIAN_RADIUS));
+  }
+
+  #[test]
+  fn pillars_block_musicians() {
+    let mut problem: ProblemSpec = serde_json::from_str(PROBLEM_JSON).unwrap();
+    assert!(problem.pillars.is_empty());
+
+    // sits on the line between attendee 0 at (100, 500) and musician 0 at (590, 10)
+    problem.pillars.push(Pillar { center: [345.0, 255.0], radius: 10.0 });
+
+    let solution = example_solution();
+    assert!(!line_of_sight(&problem, &solution.placements, 0, problem.attendees[0].position));
+    assert!(score(&problem, &solution).total < 5343);
+  }
+}
@@ -0,0 +1,259 @@
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use crate::models::Solution;
+use crate::contest::{Evaluation, Submitted};
+
+/// One solution as it was produced, with everything needed to reproduce or compare it
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Record {
+  pub problem_id: u32,
+  pub score: i64,
+  pub optimizer: String,
+  pub parameters: serde_json::Value,
+  /// Milliseconds since the unix epoch
+  pub timestamp: u64,
+  pub solution: Solution,
+  /// Official evaluation, when the solution was submitted
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub submission: Option<Submitted>,
+}
+
+/// Milliseconds since the unix epoch
+fn now() -> u64 {
+  SystemTime::now().duration_since(UNIX_EPOCH)
+    .map_or(0, |elapsed| elapsed.as_millis() as u64)
+}
+
+impl Record {
+  pub fn new(problem_id: u32, score: i64, optimizer: impl Into<String>, parameters: serde_json::Value, solution: Solution) -> Self {
+    Record {
+      problem_id,
+      score,
+      optimizer: optimizer.into(),
+      parameters,
+      timestamp: now(),
+      solution,
+      submission: None,
+    }
+  }
+
+  /// A copy of this record stamped now, carrying the evaluation of its submission
+  pub fn submitted(&self, submission: Submitted) -> Self {
+    Record {
+      timestamp: now(),
+      submission: Some(submission),
+      ..self.clone()
+    }
+  }
+}
+
+/// Parses the id out of a `problem-<id>.json` file name
+pub fn problem_id(path: &Path) -> Option<u32> {
+  path.file_name()?.to_str()?
+    .strip_prefix("problem-")?
+    .strip_suffix(".json")?
+    .parse().ok()
+}
+
+/// Solutions kept on disk as `<root>/problem-<id>/<timestamp>-<optimizer>.json`, with the
+/// highest scoring one copied to `best.json` in the same directory
+pub struct SolutionStore {
+  root: PathBuf,
+}
+
+impl SolutionStore {
+  pub fn open(root: impl Into<PathBuf>) -> Self {
+    SolutionStore { root: root.into() }
+  }
+
+  pub fn root(&self) -> &Path {
+    &self.root
+  }
+
+  fn problem_dir(&self, problem_id: u32) -> PathBuf {
+    self.root.join(format!("problem-{}", problem_id))
+  }
+
+  fn read(path: &Path) -> Result<Record, anyhow::Error> {
+    let json = std::fs::read_to_string(path)
+      .with_context(|| format!("reading {}", path.display()))?;
+
+    Ok(serde_json::from_str(&json)?)
+  }
+
+  /// Saves a record and promotes it to best if it beats the stored best. Returns whether it was promoted.
+  pub fn record(&self, record: &Record) -> Result<bool, anyhow::Error> {
+    let dir = self.problem_dir(record.problem_id);
+    std::fs::create_dir_all(&dir)?;
+
+    let json = serde_json::to_vec(record)?;
+
+    let mut path = dir.join(format!("{}-{}.json", record.timestamp, record.optimizer));
+    let mut attempt = 1;
+
+    while path.exists() {
+      path = dir.join(format!("{}-{}-{}.json", record.timestamp, record.optimizer, attempt));
+      attempt += 1;
+    }
+
+    std::fs::write(path, &json)?;
+
+    let promote = match self.best(record.problem_id)? {
+      Some(best) => record.score > best.score,
+      None => true,
+    };
+
+    if promote {
+      std::fs::write(dir.join("best.json"), &json)?;
+    }
+
+    Ok(promote)
+  }
+
+  pub fn best(&self, problem_id: u32) -> Result<Option<Record>, anyhow::Error> {
+    let path = self.problem_dir(problem_id).join("best.json");
+
+    if !path.exists() {
+      return Ok(None)
+    }
+
+    Ok(Some(Self::read(&path)?))
+  }
+
+  /// Every record of a problem, oldest first
+  pub fn history(&self, problem_id: u32) -> Result<Vec<Record>, anyhow::Error> {
+    let dir = self.problem_dir(problem_id);
+
+    if !dir.exists() {
+      return Ok(Vec::new())
+    }
+
+    let mut records = Vec::new();
+
+    for entry in std::fs::read_dir(dir)? {
+      let path = entry?.path();
+
+      if path.file_name().is_some_and(|name| name != "best.json") {
+        records.push(Self::read(&path)?);
+      }
+    }
+
+    records.sort_by_key(|record| record.timestamp);
+
+    Ok(records)
+  }
+
+  /// Most recent record the official scorer accepted
+  pub fn last_submitted(&self, problem_id: u32) -> Result<Option<Record>, anyhow::Error> {
+    Ok(self.history(problem_id)?.into_iter().rev()
+      .find(|record| matches!(record.submission, Some(Submitted { evaluation: Evaluation::Success(_), .. }))))
+  }
+
+  /// Whether the most recent submission of a record's solution was rejected by the official scorer
+  pub fn rejected(&self, record: &Record) -> Result<bool, anyhow::Error> {
+    let last = self.history(record.problem_id)?.into_iter().rev()
+      .filter(|submitted| submitted.solution == record.solution)
+      .find_map(|submitted| submitted.submission);
+
+    Ok(matches!(last, Some(Submitted { evaluation: Evaluation::Failure(_), .. })))
+  }
+
+  pub fn problem_ids(&self) -> Result<Vec<u32>, anyhow::Error> {
+    if !self.root.exists() {
+      return Ok(Vec::new())
+    }
+
+    let mut ids: Vec<u32> = Vec::new();
+
+    for entry in std::fs::read_dir(&self.root)? {
+      let name = entry?.file_name();
+
+      if let Some(id) = name.to_str().and_then(|name| name.strip_prefix("problem-")).and_then(|id| id.parse().ok()) {
+        ids.push(id);
+      }
+    }
+
+    ids.sort();
+
+    Ok(ids)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::models::Position;
+  use super::*;
+
+  fn record(score: i64) -> Record {
+    let solution = Solution {
+      placements: vec![Position { x: 1.0, y: 2.0 }],
+      volumes: None,
+    };
+
+    Record::new(7, score, "test", serde_json::json!({ "score": score }), solution)
+  }
+
+  #[test]
+  fn only_promotes_improvements() {
+    let dir = tempfile::tempdir().unwrap();
+    let store = SolutionStore::open(dir.path());
+
+    assert_eq!(store.best(7).unwrap(), None);
+
+    assert!(store.record(&record(10)).unwrap());
+    assert!(!store.record(&record(5)).unwrap());
+    assert!(store.record(&record(20)).unwrap());
+    assert!(!store.record(&record(20)).unwrap());
+
+    assert_eq!(store.best(7).unwrap().unwrap().score, 20);
+    assert_eq!(store.history(7).unwrap().len(), 4);
+    assert_eq!(store.problem_ids().unwrap(), vec![7]);
+  }
+
+  #[test]
+  fn tracks_last_accepted_submission() {
+    let dir = tempfile::tempdir().unwrap();
+    let store = SolutionStore::open(dir.path());
+
+    let submitted = |record: &Record, evaluation| record.submitted(Submitted { id: "id".into(), evaluation });
+
+    store.record(&record(10)).unwrap();
+    assert_eq!(store.last_submitted(7).unwrap(), None);
+
+    store.record(&submitted(&record(10), Evaluation::Success(10.0))).unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(2));
+    store.record(&submitted(&record(20), Evaluation::Failure("rejected".into()))).unwrap();
+
+    assert_eq!(store.last_submitted(7).unwrap().unwrap().score, 10);
+  }
+
+  #[test]
+  fn remembers_rejections() {
+    let dir = tempfile::tempdir().unwrap();
+    let store = SolutionStore::open(dir.path());
+
+    let submitted = |record: &Record, evaluation| record.submitted(Submitted { id: "id".into(), evaluation });
+    let best = record(20);
+
+    store.record(&best).unwrap();
+    assert!(!store.rejected(&best).unwrap());
+
+    std::thread::sleep(std::time::Duration::from_millis(2));
+    store.record(&submitted(&best, Evaluation::Failure("rejected".into()))).unwrap();
+    assert!(store.rejected(&best).unwrap());
+
+    // a later successful retry clears it
+    std::thread::sleep(std::time::Duration::from_millis(2));
+    store.record(&submitted(&best, Evaluation::Success(20.0))).unwrap();
+    assert!(!store.rejected(&best).unwrap());
+  }
+
+  #[test]
+  fn parses_problem_ids() {
+    assert_eq!(problem_id(Path::new("problems/problem-42.json")), Some(42));
+    assert_eq!(problem_id(Path::new("problems/solution-problem-1.json")), None);
+    assert_eq!(problem_id(Path::new("problems/example.json")), None);
+  }
+}
@@ -0,0 +1,174 @@
+use crate::models::{Position, ProblemSpec, Solution, MAX_VOLUME};
+use crate::optimizer::MusicianId;
+use crate::scoring;
+
+/// Closest two musicians may stand to each other
+pub const MUSICIAN_SPACING: f64 = 10.0;
+
+/// Distance musicians must keep from the stage edges
+pub const STAGE_MARGIN: f32 = 10.0;
+
+#[derive(Clone, Debug, PartialEq, thiserror::Error)]
+pub enum Violation {
+  #[error("expected {expected} placements but found {actual}")]
+  PlacementCount { expected: usize, actual: usize },
+  #[error("expected {expected} volumes but found {actual}")]
+  VolumeCount { expected: usize, actual: usize },
+  #[error("{musician:?} has volume {volume} outside of 0..={MAX_VOLUME}")]
+  VolumeOutOfRange { musician: MusicianId, volume: f64 },
+  #[error("{musician:?} at ({}, {}) is outside the stage margin", position.x, position.y)]
+  OffStage { musician: MusicianId, position: Position },
+  #[error("{first:?} and {second:?} are {distance:.3} apart, closer than {MUSICIAN_SPACING}")]
+  TooClose { first: MusicianId, second: MusicianId, distance: f64 },
+}
+
+impl Violation {
+  /// Musicians at fault, empty for violations about the solution as a whole
+  pub fn musicians(&self) -> Vec<MusicianId> {
+    match *self {
+      Violation::PlacementCount { .. } | Violation::VolumeCount { .. } => Vec::new(),
+      Violation::VolumeOutOfRange { musician, .. } | Violation::OffStage { musician, .. } => vec![musician],
+      Violation::TooClose { first, second, .. } => vec![first, second],
+    }
+  }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("solution has {} constraint violation(s)", .0.len())]
+pub struct InvalidSolution(pub Vec<Violation>);
+
+/// Bottom left and top right corners of the area musicians may stand in
+pub fn stage_bounds(problem: &ProblemSpec) -> (Position, Position) {
+  let bottom_left = Position {
+    x: problem.stage_bottom_left[0] + STAGE_MARGIN,
+    y: problem.stage_bottom_left[1] + STAGE_MARGIN,
+  };
+
+  let top_right = Position {
+    x: problem.stage_bottom_left[0] + problem.stage_width - STAGE_MARGIN,
+    y: problem.stage_bottom_left[1] + problem.stage_height - STAGE_MARGIN,
+  };
+
+  (bottom_left, top_right)
+}
+
+pub fn on_stage(problem: &ProblemSpec, position: Position) -> bool {
+  let (bottom_left, top_right) = stage_bounds(problem);
+
+  (bottom_left.x..=top_right.x).contains(&position.x) && (bottom_left.y..=top_right.y).contains(&position.y)
+}
+
+/// Whether `musician` could move to `position` without breaking any placement constraint
+pub fn fits(problem: &ProblemSpec, placements: &[Position], musician: usize, position: Position) -> bool {
+  on_stage(problem, position) && placements.iter().enumerate()
+    .filter(|&(other, _)| other != musician)
+    .all(|(_, &other)| scoring::dist_squared(position, other) >= MUSICIAN_SPACING * MUSICIAN_SPACING)
+}
+
+pub fn violations(problem: &ProblemSpec, solution: &Solution) -> Vec<Violation> {
+  let mut violations = Vec::new();
+  let placements = &solution.placements;
+
+  if placements.len() != problem.musicians.len() {
+    violations.push(Violation::PlacementCount {
+      expected: problem.musicians.len(),
+      actual: placements.len(),
+    });
+  }
+
+  if let Some(volumes) = &solution.volumes {
+    if volumes.len() != placements.len() {
+      violations.push(Violation::VolumeCount {
+        expected: placements.len(),
+        actual: volumes.len(),
+      });
+    }
+
+    for (idx, &volume) in volumes.iter().enumerate() {
+      if !(0.0..=MAX_VOLUME).contains(&volume) {
+        violations.push(Violation::VolumeOutOfRange { musician: MusicianId(idx), volume });
+      }
+    }
+  }
+
+  for (idx, &position) in placements.iter().enumerate() {
+    if !on_stage(problem, position) {
+      violations.push(Violation::OffStage { musician: MusicianId(idx), position });
+    }
+  }
+
+  for (first, &p1) in placements.iter().enumerate() {
+    for (second, &p2) in placements.iter().enumerate().skip(first + 1) {
+      let dist_sq = scoring::dist_squared(p1, p2);
+
+      if dist_sq < MUSICIAN_SPACING * MUSICIAN_SPACING {
+        violations.push(Violation::TooClose {
+          first: MusicianId(first),
+          second: MusicianId(second),
+          distance: dist_sq.sqrt(),
+        });
+      }
+    }
+  }
+
+  violations
+}
+
+pub fn validate(problem: &ProblemSpec, solution: &Solution) -> Result<(), InvalidSolution> {
+  let violations = violations(problem, solution);
+
+  if violations.is_empty() {
+    Ok(())
+  } else {
+    Err(InvalidSolution(violations))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const PROBLEM_JSON: &str = include_str!("../problems/example.json");
+
+  #[test]
+  fn accepts_example_solution() {
+    let problem: ProblemSpec = serde_json::from_str(PROBLEM_JSON).unwrap();
+    let solution = Solution {
+      placements: vec![
+        Position { x: 590.0, y: 10.0 },
+        Position { x: 1100.0, y: 100.0 },
+        Position { x: 1100.0, y: 150.0 },
+      ],
+      volumes: None,
+    };
+
+    assert!(validate(&problem, &solution).is_ok());
+  }
+
+  #[test]
+  fn reports_every_violation() {
+    let problem: ProblemSpec = serde_json::from_str(PROBLEM_JSON).unwrap();
+    let solution = Solution {
+      placements: vec![
+        Position { x: 600.0, y: 100.0 },
+        Position { x: 609.0, y: 100.0 },
+        Position { x: 505.0, y: 100.0 },
+        Position { x: 700.0, y: 100.0 },
+      ],
+      volumes: Some(vec![1.0, 11.0, 1.0]),
+    };
+
+    let violations = violations(&problem, &solution);
+
+    assert_eq!(violations, vec![
+      Violation::PlacementCount { expected: 3, actual: 4 },
+      Violation::VolumeCount { expected: 4, actual: 3 },
+      Violation::VolumeOutOfRange { musician: MusicianId(1), volume: 11.0 },
+      Violation::OffStage { musician: MusicianId(2), position: Position { x: 505.0, y: 100.0 } },
+      Violation::TooClose { first: MusicianId(0), second: MusicianId(1), distance: 9.0 },
+    ]);
+
+    let at_fault: Vec<MusicianId> = violations.iter().flat_map(Violation::musicians).collect();
+    assert_eq!(at_fault, vec![MusicianId(1), MusicianId(2), MusicianId(0), MusicianId(1)]);
+  }
+}